@@ -1,5 +1,3 @@
-#![feature(async_closure)]
-
 use std::ops::Add;
 
 use bevy::{app::AppExit, prelude::*};
@@ -27,7 +25,7 @@ enum ToggleableState {
 fn main() {
     let mut app = App::new();
     app.add_plugins(DefaultPlugins);
-    app.add_plugins(FlowTasksPlugin);
+    app.add_plugins(FlowTasksPlugin::default());
 
     app.add_event::<TaskComplete>();
     app.init_state::<ToggleableState>();
@@ -43,19 +41,19 @@ fn main() {
 
 
 fn start_task(mut tasks: FlowTaskManager) {
-    tasks.start(async |mut ctx: FlowContext| {
+    tasks.start(async |ctx: FlowContext| {
         info!("Flow Task Started");
 
-        ctx.set_state(ToggleableState::A);
+        ctx.set_state(ToggleableState::A).await;
         info!("ToggleableState == A");
 
-        ctx.set_state(ToggleableState::B);
+        ctx.set_state(ToggleableState::B).await;
         info!("ToggleableState == B");
 
-        ctx.set_state(ToggleableState::C);
+        ctx.set_state(ToggleableState::C).await;
         info!("ToggleableState == C");
 
-        ctx.send_event(TaskComplete);
+        ctx.send_event(TaskComplete).await;
         info!("Task Complete!");
     });
 }
@@ -90,7 +88,7 @@ fn complete(
         for v in ms {
             print!("{v}ms\t");
         }
-        print!("\n");
+        println!();
 
         exit.send(AppExit::Success);
     }
@@ -0,0 +1,190 @@
+//! Extensions to bevy's [`App`] for setting up flows
+
+use std::{future::Future, sync::Mutex};
+
+use bevy::prelude::*;
+
+use crate::{
+    context::FlowContext,
+    error::FlowOutcome,
+    event::{start_on_event, EventFlowPolicy},
+    flow::Flow,
+    plugin::{start_in_world, state_scope, FlowOptions, FlowTaskSystemSet},
+    registry::FlowRegistry,
+    runner::FlowTaskId,
+    start::StartFlowExt,
+};
+
+
+/// Adds methods for setting up flows to [`App`]
+pub trait FlowAppExt {
+    /// Register a [`Flow`] type, so it shows up in the [`FlowRegistry`], and
+    /// can be started by name with
+    /// [`FlowTaskManager::start_registered`](crate::plugin::FlowTaskManager::start_registered).
+    ///
+    /// The flow is registered as the [name](Flow::name) of its default value,
+    /// and that value is what gets started.
+    ///
+    /// ```rust
+    /// # use bevy::prelude::*;
+    /// # use bevy_flow::prelude::*;
+    /// #[derive(Default)]
+    /// struct LoadLevel;
+    ///
+    /// impl Flow for LoadLevel {
+    ///     type Input = u32;
+    ///     type Output = ();
+    ///
+    ///     fn name(&self) -> std::borrow::Cow<'static, str> {
+    ///         "load_level".into()
+    ///     }
+    ///
+    ///     async fn run(self, ctx: FlowContext, level: u32) {
+    ///         // ...
+    ///     }
+    /// }
+    ///
+    /// App::new()
+    ///     .add_plugins(FlowTasksPlugin::default())
+    ///     .register_flow::<LoadLevel>()
+    ///     .add_systems(Startup, |mut flows: FlowTaskManager| {
+    ///         flows.start_registered("load_level", 3_u32).unwrap();
+    ///     });
+    /// ```
+    fn register_flow<F>(&mut self) -> &mut Self
+    where
+        F: Flow + Default,
+        F::Output: FlowOutcome;
+
+    /// Start a [`Flow`] during the [`Startup`] schedule
+    ///
+    /// ```rust
+    /// # use bevy::prelude::*;
+    /// # use bevy_flow::prelude::*;
+    /// async fn load_settings(ctx: FlowContext) {
+    ///     // ...
+    /// }
+    ///
+    /// App::new()
+    ///     .add_plugins(FlowTasksPlugin::default())
+    ///     .add_startup_flow(load_settings);
+    /// ```
+    fn add_startup_flow<F>(&mut self, flow: F) -> &mut Self
+    where
+        F: Flow<Input = ()>,
+        F::Output: FlowOutcome;
+
+    /// Start a copy of `flow` each time the app enters `state`, which is
+    /// cancelled when the app leaves it again.
+    /// See [`FlowTaskManager::start_scoped`](crate::plugin::FlowTaskManager::start_scoped)
+    ///
+    /// ```rust
+    /// # use bevy::prelude::*;
+    /// # use bevy_flow::prelude::*;
+    /// #[derive(Clone, Copy, Debug, Default, Hash, PartialEq, Eq, States)]
+    /// enum Menu {
+    ///     #[default]
+    ///     Main,
+    ///     Settings,
+    /// }
+    ///
+    /// async fn animate_settings(ctx: FlowContext) {
+    ///     loop {
+    ///         ctx.next_frame().await;
+    ///         // ...
+    ///     }
+    /// }
+    ///
+    /// App::new()
+    ///     .add_plugins(FlowTasksPlugin::default())
+    ///     .init_state::<Menu>()
+    ///     .add_flow_on_enter(Menu::Settings, animate_settings);
+    /// ```
+    fn add_flow_on_enter<S, F>(&mut self, state: S, flow: F) -> &mut Self
+    where
+        S: States,
+        F: Flow<Input = ()> + Clone,
+        F::Output: FlowOutcome;
+
+    /// Start a flow for each `E` event that's sent, which is given a copy of
+    /// the event. `policy` decides what happens to events that arrive while
+    /// a flow is still running.
+    ///
+    /// Events are read during [`Update`], before [`FlowTaskSystemSet`].
+    ///
+    /// ```rust
+    /// # use bevy::prelude::*;
+    /// # use bevy_flow::prelude::*;
+    /// # #[derive(Resource)] struct Saved(u32);
+    /// #[derive(Event, Clone)]
+    /// struct SaveGame {
+    ///     slot: u32,
+    /// }
+    ///
+    /// App::new()
+    ///     .add_plugins(FlowTasksPlugin::default())
+    ///     .add_flow_on_event(EventFlowPolicy::Queue, |ctx, evt: SaveGame| async move {
+    ///         // ...
+    ///         ctx.insert_resource(Saved(evt.slot)).await;
+    ///     });
+    /// ```
+    fn add_flow_on_event<E, Func, Fut>(&mut self, policy: EventFlowPolicy, flow: Func) -> &mut Self
+    where
+        E: Event + Clone,
+        Func: Fn(FlowContext, E) -> Fut + Send + Sync + 'static,
+        Fut: Future + Send,
+        Fut::Output: FlowOutcome;
+}
+
+impl FlowAppExt for App {
+    fn register_flow<F>(&mut self) -> &mut Self
+    where
+        F: Flow + Default,
+        F::Output: FlowOutcome,
+    {
+        self.world_mut()
+            .get_resource_or_insert_with(FlowRegistry::default)
+            .register::<F>();
+        self
+    }
+
+    fn add_startup_flow<F>(&mut self, flow: F) -> &mut Self
+    where
+        F: Flow<Input = ()>,
+        F::Output: FlowOutcome,
+    {
+        // systems have to be `Sync`, and flows don't have to be
+        let flow = Mutex::new(Some(flow));
+        self.add_systems(Startup, move |world: &mut World| {
+            let flow = flow.lock().unwrap_or_else(|e| e.into_inner()).take();
+            if let Some(flow) = flow {
+                world.start_flow(flow);
+            }
+        })
+    }
+
+    fn add_flow_on_enter<S, F>(&mut self, state: S, flow: F) -> &mut Self
+    where
+        S: States,
+        F: Flow<Input = ()> + Clone,
+        F::Output: FlowOutcome,
+    {
+        let flow = Mutex::new(flow);
+        self.add_systems(OnEnter(state.clone()), move |world: &mut World| {
+            let flow = flow.lock().unwrap_or_else(|e| e.into_inner()).clone();
+            let options = FlowOptions { scope: Some(state_scope(state.clone())), ..default() };
+            start_in_world(world, FlowTaskId::next(), flow, (), options);
+        })
+    }
+
+    fn add_flow_on_event<E, Func, Fut>(&mut self, policy: EventFlowPolicy, flow: Func) -> &mut Self
+    where
+        E: Event + Clone,
+        Func: Fn(FlowContext, E) -> Fut + Send + Sync + 'static,
+        Fut: Future + Send,
+        Fut::Output: FlowOutcome,
+    {
+        self.add_event::<E>()
+            .add_systems(Update, start_on_event(policy, flow).before(FlowTaskSystemSet))
+    }
+}
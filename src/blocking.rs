@@ -0,0 +1,172 @@
+//! Blocking versions of the [`FlowContext`] helpers that use the [`World`]
+//!
+//! Every helper on [`FlowContext`] is `async`, so waiting for the `World`
+//! lets the executor run other flows in the meantime. The versions here block
+//! the thread they're called on until the `World` has been lent and the work
+//! is done instead, which can be handy when porting synchronous code.
+//!
+//! **NOTE:** Only use these on flows run by [`FlowExecutor::Thread`], where each
+//! flow has a thread of its own. On a pool, blocking a thread also stalls every
+//! other flow sharing it, and can deadlock the pool if all of its threads end up
+//! waiting on each other.
+//!
+//! ```rust,no_run
+//! # use bevy::prelude::*;
+//! # use bevy_flow::prelude::*;
+//! use bevy_flow::blocking::FlowContextBlockingExt;
+//!
+//! # #[derive(Resource, Clone)] struct Seed(u64);
+//! # fn generate(_seed: u64) -> u64 { 0 }
+//! async fn legacy(ctx: FlowContext) {
+//!     let ctx = ctx.blocking();
+//!     let seed = ctx.copy_resource::<Seed>();
+//!     ctx.insert_resource(Seed(generate(seed.0)));
+//! }
+//! ```
+//!
+//! [`FlowExecutor::Thread`]: crate::runner::FlowExecutor::Thread
+
+use bevy::{
+    ecs::{event::EventId, system::SystemParam},
+    prelude::*,
+    state::state::FreelyMutableState,
+    tasks::block_on,
+};
+
+use crate::{context::{FlowContext, WorldRef}, error::FlowError};
+
+
+/// Adds [`blocking`](Self::blocking) to [`FlowContext`]
+pub trait FlowContextBlockingExt {
+    /// Use the blocking versions of this flow's helpers.
+    /// See the [module docs](crate::blocking) before using this.
+    fn blocking(&self) -> BlockingContext<'_>;
+}
+
+impl FlowContextBlockingExt for FlowContext {
+    fn blocking(&self) -> BlockingContext<'_> {
+        BlockingContext { ctx: self }
+    }
+}
+
+/// A [`FlowContext`] whose helpers block until they're done, instead of being
+/// awaited. Created by [`FlowContextBlockingExt::blocking`]
+///
+/// Each method does the same thing as the one of the same name on `FlowContext`
+#[derive(Clone, Copy)]
+pub struct BlockingContext<'a> {
+    ctx: &'a FlowContext,
+}
+
+impl<'a> BlockingContext<'a> {
+    /// The [`FlowContext`] this was created from
+    pub fn context(&self) -> &'a FlowContext {
+        self.ctx
+    }
+
+    /// See [`FlowContext::borrow`]
+    pub fn borrow(&self) -> WorldRef<'a> {
+        block_on(self.ctx.borrow())
+    }
+
+    /// See [`FlowContext::try_borrow`]
+    ///
+    /// # Errors
+    ///
+    /// - [`FlowError::Cancelled`] if the flow has been cancelled
+    /// - [`FlowError::Disconnected`] if the app has stopped lending the `World`
+    pub fn try_borrow(&self) -> Result<WorldRef<'a>, FlowError> {
+        block_on(self.ctx.try_borrow())
+    }
+
+    /// See [`FlowContext::with_world`]
+    pub fn with_world<Ret>(&self, call: impl FnOnce(&mut World) -> Ret) -> Ret {
+        block_on(self.ctx.with_world(call))
+    }
+
+    /// See [`FlowContext::batch`]
+    pub fn batch<Ret>(&self, call: impl FnOnce(&mut WorldRef) -> Ret) -> Ret {
+        block_on(self.ctx.batch(call))
+    }
+
+    /// See [`FlowContext::with`]
+    ///
+    /// # Panics
+    ///
+    /// Panics for the same reasons as [`FlowContext::with`]
+    pub fn with<'s, Sys, Out, Params>(&self, system: Sys) -> Out
+    where
+        Params: SystemParam + 'static,
+        Sys: FnOnce(Params::Item<'s, 's>) -> Out + 's,
+        Out: Send + 's
+    {
+        block_on(self.ctx.with::<Sys, Out, Params>(system))
+    }
+
+    /// See [`FlowContext::copy_resource`]
+    ///
+    /// # Panics
+    ///
+    /// Panics if the Resource doesn't exist. See [`try_copy_resource`](Self::try_copy_resource)
+    pub fn copy_resource<R: Resource + Clone>(&self) -> R {
+        block_on(self.ctx.copy_resource())
+    }
+
+    /// See [`FlowContext::try_copy_resource`]
+    ///
+    /// # Errors
+    ///
+    /// - [`FlowError::MissingResource`] if the resource doesn't exist
+    /// - [`FlowError::Cancelled`] if the flow has been cancelled
+    pub fn try_copy_resource<R: Resource + Clone>(&self) -> Result<R, FlowError> {
+        block_on(self.ctx.try_copy_resource())
+    }
+
+    /// See [`FlowContext::insert_resource`]
+    pub fn insert_resource<R: Resource>(&self, resource: R) {
+        block_on(self.ctx.insert_resource(resource))
+    }
+
+    /// See [`FlowContext::set_state`]
+    pub fn set_state<S: States + FreelyMutableState>(&self, new: S) {
+        block_on(self.ctx.set_state(new))
+    }
+
+    /// See [`FlowContext::send_event`]
+    ///
+    /// # Panics
+    ///
+    /// Panics if the event was never added. See [`try_send_event`](Self::try_send_event)
+    pub fn send_event<E: Event>(&self, event: E) -> EventId<E> {
+        block_on(self.ctx.send_event(event))
+    }
+
+    /// See [`FlowContext::try_send_event`]
+    ///
+    /// # Errors
+    ///
+    /// - [`FlowError::MissingEvent`] if the event hasn't been added to the app
+    /// - [`FlowError::Cancelled`] if the flow has been cancelled
+    pub fn try_send_event<E: Event>(&self, event: E) -> Result<EventId<E>, FlowError> {
+        block_on(self.ctx.try_send_event(event))
+    }
+
+    /// See [`FlowContext::get_state`]
+    ///
+    /// # Panics
+    ///
+    /// Panics if the state doesn't exist. See [`try_get_state`](Self::try_get_state)
+    pub fn get_state<S: States>(&self) -> S {
+        block_on(self.ctx.get_state())
+    }
+
+    /// See [`FlowContext::try_get_state`]
+    ///
+    /// # Errors
+    ///
+    /// - [`FlowError::MissingState`] if the state hasn't been added to the app
+    /// - [`FlowError::Cancelled`] if the flow has been cancelled
+    pub fn try_get_state<S: States>(&self) -> Result<S, FlowError> {
+        block_on(self.ctx.try_get_state())
+    }
+}
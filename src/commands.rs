@@ -0,0 +1,110 @@
+//! Changes flows make to the [`World`] without waiting for it
+
+use std::panic::{catch_unwind, AssertUnwindSafe};
+
+use async_channel::{unbounded, Receiver, Sender};
+use bevy::{ecs::world::Command, prelude::*, state::state::FreelyMutableState};
+
+use crate::error::FlowError;
+
+
+/// A [`Command`] queued by a flow
+pub(crate) type FlowCommand = Box<dyn FnOnce(&mut World) + Send>;
+
+/// Queues [`Command`]s from a flow, without waiting for the [`World`]. 
+/// Created by [`FlowContext::commands`](crate::context::FlowContext::commands)
+/// 
+/// Commands are applied by [`FlowTasksPlugin`](crate::plugin::FlowTasksPlugin)
+/// each time it lends the `World` to flows, in the order they were queued, 
+/// and always before the flow that queued them is lent the `World` again.
+/// So while the flow keeps working, its changes land in the background.
+/// 
+/// ```rust,no_run
+/// # use bevy::prelude::*;
+/// # use bevy_flow::prelude::*;
+/// # #[derive(Resource)] struct Progress(f32);
+/// # fn crunch(_chunk: usize) { }
+/// async fn process(ctx: FlowContext) {
+///     let mut commands = ctx.commands();
+///     for chunk in 0..100 {
+///         crunch(chunk);
+///         commands.insert_resource(Progress(chunk as f32 / 100.0));
+///     }
+/// }
+/// ```
+#[derive(Clone)]
+pub struct FlowCommands {
+    send: Sender<FlowCommand>,
+}
+
+impl FlowCommands {
+    pub(crate) fn new(send: Sender<FlowCommand>) -> Self {
+        Self { send }
+    }
+
+    /// Queue any [`Command`], including closures that take `&mut World`
+    pub fn add(&mut self, command: impl Command) -> &mut Self {
+        // only fails if the app is gone, in which case there's nothing to apply it to
+        let _ = self.send.try_send(Box::new(move |world: &mut World| command.apply(world)));
+        self
+    }
+
+    /// Queue inserting a [`Resource`], replacing any existing one
+    pub fn insert_resource<R: Resource>(&mut self, resource: R) -> &mut Self {
+        self.add(move |world: &mut World| world.insert_resource(resource))
+    }
+
+    /// Queue removing a [`Resource`]
+    pub fn remove_resource<R: Resource>(&mut self) -> &mut Self {
+        self.add(|world: &mut World| { world.remove_resource::<R>(); })
+    }
+
+    /// Queue spawning an entity
+    pub fn spawn(&mut self, bundle: impl Bundle) -> &mut Self {
+        self.add(move |world: &mut World| { world.spawn(bundle); })
+    }
+
+    /// Queue sending an [`Event`]. If the event hasn't been added to the app, 
+    /// an error is logged when the command is applied.
+    pub fn send_event<E: Event>(&mut self, event: E) -> &mut Self {
+        self.add(move |world: &mut World| { world.send_event(event); })
+    }
+
+    /// Queue changing a [`State`], like [`NextState::set`]. If the state isn't
+    /// present in the app it is added.
+    pub fn set_state<S: States + FreelyMutableState>(&mut self, new: S) -> &mut Self {
+        self.add(move |world: &mut World| {
+            if let Some(mut next) = world.get_resource_mut::<NextState<S>>() {
+                next.set(new);
+            }
+            else {
+                world.insert_resource(State::new(new));
+                world.insert_resource(NextState::<S>::default())
+            }
+        })
+    }
+}
+
+/// Where the commands queued by every flow end up
+#[derive(Clone)]
+pub(crate) struct FlowCommandQueue {
+    recv: Receiver<FlowCommand>,
+}
+
+impl FlowCommandQueue {
+    /// Creates the queue, and the [`Sender`] flows should be given
+    pub(crate) fn new() -> (Self, Sender<FlowCommand>) {
+        let (send, recv) = unbounded();
+        (Self { recv }, send)
+    }
+
+    /// Apply every queued command. A panicking command is logged, and 
+    /// doesn't stop the rest from being applied
+    pub(crate) fn apply(&self, world: &mut World) {
+        while let Ok(command) = self.recv.try_recv() {
+            if let Err(payload) = catch_unwind(AssertUnwindSafe(|| command(world))) {
+                error!("Flow command failed: {}", FlowError::from_panic(payload));
+            }
+        }
+    }
+}
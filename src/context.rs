@@ -1,13 +1,36 @@
-//!
+//! The handle flows use to reach into the bevy [`World`]
 
-use std::{any::type_name, ops::{Deref, DerefMut}};
+use std::{
+    any::type_name, 
+    fmt::Display,
+    future::{pending, poll_fn, Future, IntoFuture}, 
+    ops::{Deref, DerefMut}, 
+    panic::{catch_unwind, resume_unwind, AssertUnwindSafe}, 
+    sync::Arc, 
+    task::Poll, 
+    time::Duration
+};
 
 use bevy::{
-    asset::{AssetPath, LoadedFolder}, ecs::{event::EventId, system::{SystemParam, SystemState}}, prelude::*, state::state::FreelyMutableState, tasks::block_on
+    asset::{AssetPath, LoadedFolder}, 
+    ecs::{event::EventId, schedule::ScheduleLabel, system::{SystemParam, SystemState}}, 
+    prelude::*, 
+    state::state::FreelyMutableState, 
+    tasks::{block_on, futures_lite::{stream, Stream}}
 };
-use async_channel::{Receiver, Sender};
+use async_channel::{bounded, Receiver, Sender, TrySendError};
 
-use crate::runner::{LTMsg, LTResult};
+use crate::{
+    commands::{FlowCommand, FlowCommands},
+    cursor::EventCursors,
+    error::FlowError, 
+    flow::Flow,
+    handle::FlowTaskHandle,
+    plugin::start_child,
+    runner::{FlowControl, FlowLinks, FlowTaskId, LTMsg, LTResult}, 
+    select::FlowSelect,
+    waiter::{FlowClock, FlowNow, WaitCheck}
+};
 
 
 
@@ -15,40 +38,101 @@ use crate::runner::{LTMsg, LTResult};
 
 
 
-/// Provides safe access to a bevy [`World`] in the context of
-/// 
+/// Provides safe access to a bevy [`World`] in the context of a flow
 pub struct FlowContext {
     send: Sender<LTResult>,
     recv: Receiver<LTMsg>,
     assets: Option<AssetServer>,
+    waiters: Sender<WaitCheck>,
+    commands: Sender<FlowCommand>,
+    now: Arc<FlowNow>,
+    control: Arc<FlowControl>,
+    cursors: EventCursors,
 }
 
 impl FlowContext {
     pub(crate) fn new(
         send: Sender<LTResult>,
         recv: Receiver<LTMsg>,
-        assets: Option<AssetServer>
+        links: FlowLinks,
+        control: Arc<FlowControl>,
     ) -> Self {
         Self {
             send,
             recv,
-            assets,
+            assets: links.assets,
+            waiters: links.waiters,
+            commands: links.commands,
+            now: links.now,
+            control,
+            cursors: EventCursors::default(),
         }
     }
 
-    async fn request_world(&self) -> *mut World {
-        if let Err(err) = self.send.send(LTResult::RequestingWorld).await {
-            panic!("LongTaskRunner must have dropped {err:?}");
+    async fn request_world(&self) -> Result<*mut World, FlowError> {
+        if self.control.is_cancelled() {
+            return Err(FlowError::Cancelled)
         }
 
-        match self.recv.recv().await {
-            Ok(LTMsg::World(world_ptr)) => world_ptr,
-            Err(err) => panic!("{err:?}")
+        if self.send.send(LTResult::RequestingWorld).await.is_err() {
+            return Err(FlowError::Disconnected)
+        }
+
+        // if this future is dropped before the answer arrives, like when it 
+        // loses a `select`, the runner needs to know nobody is waiting anymore
+        let mut request = PendingRequest { send: &self.send, control: &self.control, answered: false };
+        let answer = self.recv.recv().await;
+        request.answered = true;
+
+        match answer {
+            Ok(LTMsg::World(world_ptr)) => Ok(world_ptr),
+            Ok(LTMsg::Cancelled) => Err(FlowError::Cancelled),
+            Err(_) => Err(FlowError::Disconnected),
         }
     }
 
-    fn world_sync(&self) -> WorldRef<'_> {
-        block_on(self.borrow())
+    /// Wait until `check` returns something. `check` is run on the bevy side, 
+    /// roughly once a frame, so waiting doesn't need the `World` to be lent.
+    /// 
+    /// If `check` panics, the panic is carried back to the flow and resumed 
+    /// there, so it's reported like any other panic in the flow, instead of 
+    /// taking the app down with it.
+    /// 
+    /// Cancelling the flow ends the wait with [`FlowError::Cancelled`]
+    async fn wait_until<R: Send + 'static>(
+        &self,
+        mut check: impl FnMut(&World) -> Option<Result<R, FlowError>> + Send + 'static,
+    ) -> Result<R, FlowError> {
+        if self.control.is_cancelled() {
+            return Err(FlowError::Cancelled)
+        }
+
+        let (send, recv) = bounded(1);
+        let control = self.control.clone();
+        let waiter: WaitCheck = Box::new(move |world| {
+            // the flow stopped waiting
+            if send.is_closed() { return true }
+
+            let result = match control.is_cancelled() {
+                true => Ok(Err(FlowError::Cancelled)),
+                false => match catch_unwind(AssertUnwindSafe(|| check(world))) {
+                    Ok(Some(result)) => Ok(result),
+                    Ok(None) => return false,
+                    Err(payload) => Err(payload),
+                },
+            };
+            let _ = send.try_send(result);
+            true
+        });
+
+        if self.waiters.try_send(waiter).is_err() {
+            return Err(FlowError::Disconnected)
+        }
+        match recv.recv().await {
+            Ok(Ok(result)) => result,
+            Ok(Err(payload)) => resume_unwind(payload),
+            Err(_) => Err(FlowError::Disconnected),
+        }
     }
 }
 
@@ -63,31 +147,148 @@ impl FlowContext {
     /// 
     /// While this reference is held, the rest of the bevy app is halted, so be sure
     /// to periodically drop it and borrow again to prevent the main app from stuttering
+    /// 
+    /// If the flow has been cancelled, this stops the flow by unwinding out of
+    /// it. Use [`try_borrow`](Self::try_borrow) to handle cancellation yourself.
     pub async fn borrow(&self) -> WorldRef<'_> {
-        let world_ptr = self.request_world().await;
-        WorldRef {
+        match self.try_borrow().await {
+            Ok(world) => world,
+            Err(err) => stop_flow(err),
+        }
+    }
+
+    /// Same as [`borrow`](Self::borrow), but returns [`FlowError::Cancelled`] if
+    /// the flow has been cancelled, instead of stopping the flow.
+    /// 
+    /// # Errors
+    /// 
+    /// - [`FlowError::Cancelled`] if the flow has been cancelled
+    /// - [`FlowError::Disconnected`] if the app has stopped lending the `World`
+    pub async fn try_borrow(&self) -> Result<WorldRef<'_>, FlowError> {
+        let world_ptr = self.request_world().await?;
+        Ok(WorldRef {
             world: unsafe { &mut *world_ptr },
             linker: self,
-        }
+        })
     }
 
-    /// Directly use the [`World`]. While this function is running, the rest of 
+    /// Returns `true` if this flow has been asked to stop, by
+    /// [`FlowTaskManager::stop`](crate::plugin::FlowTaskManager::stop) or
+    /// [`FlowTaskHandle::cancel`](crate::handle::FlowTaskHandle::cancel).
+    /// 
+    /// Flows doing lots of work without touching the [`World`] should check 
+    /// this every now and then, and return early if it's `true`.
+    pub fn is_cancelled(&self) -> bool {
+        self.control.is_cancelled()
+    }
+
+    /// The entity this flow belongs to, if it was started with 
+    /// [`EntityFlowExt::start_flow`](crate::entity::EntityFlowExt::start_flow).
+    /// 
+    /// The flow is cancelled when the entity is despawned, so it isn't lent
+    /// the [`World`] after that.
+    pub fn entity(&self) -> Option<Entity> {
+        self.control.owner()
+    }
+
+    /// Register a closure to run if this flow is cancelled. It runs on the main 
+    /// thread with access to the [`World`], before the flow is torn down, which
+    /// makes it a good place to despawn entities or remove resources the flow
+    /// was in charge of.
+    /// 
+    /// Hooks are discarded without running if the flow finishes normally.
+    pub fn on_cancel(&self, hook: impl FnOnce(&mut World) + Send + 'static) {
+        self.control.add_cancel_hook(Box::new(hook));
+    }
+
+    /// Pin this flow to `schedule`, so from now on it's only lent the [`World`]
+    /// while that schedule runs. Like syncing with physics in [`FixedPostUpdate`], 
+    /// then going back to [`Update`] to touch the UI. 
+    /// 
+    /// If nothing lends the `World` in that schedule yet, 
+    /// [`FlowTasksPlugin`](crate::plugin::FlowTasksPlugin) adds a system to do it 
+    /// before the next frame.
+    /// 
+    /// ```rust,no_run
+    /// # use bevy::prelude::*;
+    /// # use bevy_flow::prelude::*;
+    /// # #[derive(Component)] struct Camera;
+    /// async fn cutscene(ctx: FlowContext) {
+    ///     ctx.switch_schedule(FixedPostUpdate);
+    ///     let camera_at = ctx.with_world(|world| {
+    ///         let mut cameras = world.query_filtered::<&Transform, With<Camera>>();
+    ///         cameras.single(world).translation
+    ///     }).await;
+    /// 
+    ///     ctx.use_default_schedule();
+    ///     // ...
+    /// }
+    /// ```
+    pub fn switch_schedule(&self, schedule: impl ScheduleLabel) {
+        self.control.set_schedule(Some(schedule.intern()));
+    }
+
+    /// Unpin this flow, so it's lent the [`World`] in the schedules 
+    /// [`FlowTasksPlugin`](crate::plugin::FlowTasksPlugin) was set up with.
+    /// See [`switch_schedule`](Self::switch_schedule)
+    pub fn use_default_schedule(&self) {
+        self.control.set_schedule(None);
+    }
+
+    /// Directly use the [`World`]. While `call` is running, the rest of 
     /// your bevy App is halted by an exclusive system, so don't do too much in one
     /// of these.
     /// 
+    /// **NOTE:** Be sure to use `await` on this function or it will be skipped
+    /// 
     /// # Panics
     /// 
-    /// Panics if the controling [`FlowTaskRunner`](super::runner::FlowTaskRunner) 
-    /// is dropped. This shouldn't happen
-    pub fn with_world<Ret>(&self, call: impl FnOnce(&mut World) -> Ret) -> Ret {
-        block_on(async {
-            let world_ptr = self.request_world().await;
-            let world = unsafe { &mut *world_ptr };
+    /// Stops the flow by unwinding out of it if the flow has been cancelled, or
+    /// the controling [`FlowTaskRunner`](super::runner::FlowTaskRunner) is dropped.
+    /// 
+    /// If `call` panics, the `World` is still handed back to the app.
+    pub async fn with_world<Ret>(&self, call: impl FnOnce(&mut World) -> Ret) -> Ret {
+        // the `WorldRef` hands the `World` back even if `call` panics
+        let mut world = self.borrow().await;
+        call(&mut world)
+    }
 
-            let ret = call(world);
-            self.send.send(LTResult::DoneWithWorld).await.unwrap();
-            ret
-        })
+    /// Queue changes to the [`World`] without waiting for it. The flow carries 
+    /// on right away, and the changes are applied the next time the plugin lends 
+    /// the `World` to flows, before this flow gets it again. See [`FlowCommands`]
+    pub fn commands(&self) -> FlowCommands {
+        FlowCommands::new(self.commands.clone())
+    }
+
+    /// Do several things with the [`World`] in a single loan. Each of the 
+    /// helpers like [`set_state`](Self::set_state) or [`send_event`](Self::send_event) 
+    /// waits for a loan of its own, so doing them one after another takes a frame each.
+    /// [`WorldRef`] has the same helpers, so they can be grouped instead:
+    /// 
+    /// ```rust,no_run
+    /// # use bevy::prelude::*;
+    /// # use bevy_flow::prelude::*;
+    /// # #[derive(Clone, Debug, Default, Hash, PartialEq, Eq, States)] enum Level { #[default] Loading, Playing }
+    /// # #[derive(Event)] struct LevelStarted;
+    /// # #[derive(Resource)] struct Score(u32);
+    /// async fn start_level(ctx: FlowContext) {
+    ///     ctx.batch(|world| {
+    ///         world.set_state(Level::Playing);
+    ///         world.send_event(LevelStarted);
+    ///         world.insert_resource(Score(0));
+    ///     }).await;
+    /// }
+    /// ```
+    /// 
+    /// **NOTE:** Be sure to use `await` on this function or it will be skipped
+    /// 
+    /// # Panics
+    /// 
+    /// Stops the flow by unwinding out of it if the flow has been cancelled, or
+    /// the controling [`FlowTaskRunner`](super::runner::FlowTaskRunner) is dropped.
+    pub async fn batch<Ret>(&self, call: impl FnOnce(&mut WorldRef) -> Ret) -> Ret {
+        let mut world = self.borrow().await;
+        call(&mut world)
     }
 
     /// Run a system once. This works similar to bevy's [`App::add_systems`].
@@ -98,16 +299,15 @@ impl FlowContext {
     /// 
     /// ### Example
     /// ```ignore
-    /// # #![feature(async_closure)]
     /// # use bevy::prelude::*;
     /// # use bevy_flow::prelude::*;
     /// 
-    /// flow.start(async |mut ctx: FlowContext| {
+    /// flow.start(async |ctx: FlowContext| {
     ///     // Use bevy resources, just like a system
-    ///     let ret = ctx.once(|mut events: EventReader<CursorMoved>, mut cmds: Commands| {
+    ///     let ret = ctx.with(|mut events: EventReader<CursorMoved>, mut cmds: Commands| {
     ///         // do stuff with resources
     ///         32 // return value to pass to parent flow scope
-    ///     });
+    ///     }).await;
     ///     assert_eq!(ret, 32);
     /// })
     /// ```
@@ -129,18 +329,20 @@ impl FlowContext {
     /// This doesn't support exclusive systems, so if you need to access 
     /// [`World`], use [`with_world`](Self::with_world). 
     /// 
+    /// **NOTE:** Be sure to use `await` on this function or it will be skipped
+    /// 
     /// # Panics
     /// 
     /// This for the same reasons a normal bevy system would panic:
     /// - Two or more `Query`s request access to the same [`Component`], and at least one 
     ///   of them are mutable.
     /// - A `Resource`, [`Event`], or [`State`] is requested that isn't present.
-    pub fn with<'a, Sys, Out, Params>(&self, _system: Sys) -> Out
+    pub async fn with<'a, Sys, Out, Params>(&self, _system: Sys) -> Out
     where
         Params: SystemParam + 'static,
         Sys: FnOnce(Params::Item<'a, 'a>) -> Out + 'a,
         // Sys: SystemParamFunction<()> + FnOnce(Params) -> Out + 'a,
-        Out: Send + Sync + 'a
+        Out: Send + 'a
     {
         // Sys::Param::
         self.with_world(|world| {
@@ -155,34 +357,53 @@ impl FlowContext {
             };
             state_ref.apply(world);
             out
-        })
+        }).await
     }
 
     /// Gets a copy of a [`Resource`]
     /// 
+    /// **NOTE:** Be sure to use `await` on this function or it will be skipped
+    /// 
     /// # Panics
     /// 
-    /// Panics if the Resource doesn't exist
-    pub fn copy_resource<R>(&self) -> R
+    /// Panics if the Resource doesn't exist. See [`try_copy_resource`](Self::try_copy_resource)
+    pub async fn copy_resource<R>(&self) -> R
     where
         R: Resource + Clone 
     {
-        self.with_world(|world| {
-            world.get_resource::<R>().unwrap().clone()
-        })
+        unwrap_flow(self.try_copy_resource().await)
+    }
+
+    /// Gets a copy of a [`Resource`]
+    /// 
+    /// **NOTE:** Be sure to use `await` on this function or it will be skipped
+    /// 
+    /// # Errors
+    /// 
+    /// - [`FlowError::MissingResource`] if the resource doesn't exist
+    /// - [`FlowError::Cancelled`] if the flow has been cancelled
+    pub async fn try_copy_resource<R>(&self) -> Result<R, FlowError>
+    where
+        R: Resource + Clone 
+    {
+        self.try_borrow().await?.try_copy_resource()
     }
 
     /// Inserts a new resource with the given value.
     /// 
     /// Resources are "unique" data of a given type. If you insert a 
     /// resource of a type that already exists, you will overwrite any existing data.
-    pub fn insert_resource<R>(&self, resource: R) 
+    /// 
+    /// To insert it without waiting, see [`commands`](Self::commands)
+    /// 
+    /// **NOTE:** Be sure to use `await` on this function or it will be skipped
+    pub async fn insert_resource<R>(&self, resource: R) 
     where 
         R: Resource 
     {
         self.with_world(|world| {
             world.insert_resource(resource)
-        })
+        }).await
     }
 
     /// Directly access the [`AssetServer`]. Using this doesn't effect the main apps 
@@ -190,9 +411,18 @@ impl FlowContext {
     /// 
     /// # Panics
     /// 
-    /// Panics if the [`AssetPlugin`] is not available
+    /// Panics if the [`AssetPlugin`] is not available. See [`try_asset_server`](Self::try_asset_server)
     pub fn asset_server(&self) -> &AssetServer {
-        self.assets.as_ref().unwrap()
+        unwrap_flow(self.try_asset_server())
+    }
+
+    /// Directly access the [`AssetServer`]. 
+    /// 
+    /// # Errors
+    /// 
+    /// Returns [`FlowError::NoAssetServer`] if the [`AssetPlugin`] is not available
+    pub fn try_asset_server(&self) -> Result<&AssetServer, FlowError> {
+        self.assets.as_ref().ok_or(FlowError::NoAssetServer)
     }
 
     /// Same as [`AssetServer::load`]
@@ -201,8 +431,7 @@ impl FlowContext {
     /// 
     /// Panics if the [`AssetPlugin`] is not available
     pub fn load_asset<'a, A: Asset>(&self, path: impl Into<AssetPath<'a>>) -> Handle<A> {
-        let assets = self.assets.as_ref().unwrap();
-        assets.load(path)
+        self.asset_server().load(path)
     }
 
     /// Same as [`AssetServer::load_folder`]
@@ -211,8 +440,7 @@ impl FlowContext {
     /// 
     /// Panics if the [`AssetPlugin`] is not available
     pub fn load_folder<'a>(&self, path: impl Into<AssetPath<'a>>) -> Handle<LoadedFolder> {
-        let assets = self.assets.as_ref().unwrap();
-        assets.load_folder(path)
+        self.asset_server().load_folder(path)
     }
 
     /// Schedules changing a [`State`] resource at the end of the next update cycle.
@@ -220,15 +448,10 @@ impl FlowContext {
     /// This is equivalent to calling [`NextState::set`] in a normal system
     /// 
     /// If the state is not present in the app it is added.
-    pub fn set_state<S: States + FreelyMutableState>(&self, new: S) {
-        let mut world = self.world_sync();
-        if let Some(mut next) = world.get_resource_mut::<NextState<S>>() {
-            next.set(new);
-        }
-        else {
-            world.insert_resource(State::new(new.clone()));
-            world.insert_resource(NextState::<S>::default())
-        }
+    /// 
+    /// **NOTE:** Be sure to use `await` on this function or it will be skipped
+    pub async fn set_state<S: States + FreelyMutableState>(&self, new: S) {
+        self.borrow().await.set_state(new)
     }
 
     /// Sends an [`Event`] to the game, that will be recieved on the next update cycle.
@@ -238,12 +461,25 @@ impl FlowContext {
     /// # Panics
     /// 
     /// Panics if the the event hasn't been insterted into the bevy App.
+    /// See [`try_send_event`](Self::try_send_event)
     /// 
     /// See [`App::add_event`]
-    pub fn send_event<E: Event>(&mut self, event: E) -> EventId<E> {
-        let mut world = self.world_sync();
-        let mut events = world.get_resource_mut::<Events<E>>().unwrap();
-        events.send(event)
+    /// 
+    /// **NOTE:** Be sure to use `await` on this function or it will be skipped
+    pub async fn send_event<E: Event>(&self, event: E) -> EventId<E> {
+        unwrap_flow(self.try_send_event(event).await)
+    }
+
+    /// Sends an [`Event`] to the game, that will be recieved on the next update cycle.
+    /// 
+    /// **NOTE:** Be sure to use `await` on this function or it will be skipped
+    /// 
+    /// # Errors
+    /// 
+    /// - [`FlowError::MissingEvent`] if the event hasn't been added to the app
+    /// - [`FlowError::Cancelled`] if the flow has been cancelled
+    pub async fn try_send_event<E: Event>(&self, event: E) -> Result<EventId<E>, FlowError> {
+        self.try_borrow().await?.try_send_event(event)
     }
 
     /// Get the current state
@@ -251,80 +487,99 @@ impl FlowContext {
     /// # Panics
     /// 
     /// Panics if the the State hasn't been insterted into the bevy App.
+    /// See [`try_get_state`](Self::try_get_state)
     /// 
     /// See [`App::init_state`] or [`App::insert_state`]
-    pub fn get_state<S: States>(&self) -> S {
-        let world = self.world_sync();
-        let next = world.get_resource::<State<S>>().unwrap();
-        next.get().clone()
+    /// 
+    /// **NOTE:** Be sure to use `await` on this function or it will be skipped
+    pub async fn get_state<S: States>(&self) -> S {
+        unwrap_flow(self.try_get_state().await)
+    }
+
+    /// Get the current state
+    /// 
+    /// **NOTE:** Be sure to use `await` on this function or it will be skipped
+    /// 
+    /// # Errors
+    /// 
+    /// - [`FlowError::MissingState`] if the state hasn't been added to the app
+    /// - [`FlowError::Cancelled`] if the flow has been cancelled
+    pub async fn try_get_state<S: States>(&self) -> Result<S, FlowError> {
+        self.try_borrow().await?.try_get_state()
     }
 
 
 
     /// Loads a folder, like [`AssetServer::load_folder`], then waits until the 
     /// every file in that folder is loaded, then returns a list of all
-    /// the assets loaded loaded
+    /// the assets loaded loaded. Waiting doesn't borrow the [`World`]
     /// 
     /// **NOTE:** Be sure to use `await` on this function or it will be skipped
     /// 
     /// # Panics
     /// 
     /// Panics if the [`AssetPlugin`] is not available
-    #[allow(clippy::missing_panics_doc)] // shouldn't be able to panic
     pub async fn await_folder(
         &self, 
         path: impl Into<AssetPath<'_>>
     ) -> (Handle<LoadedFolder>, LoadedFolder) 
     {
-        let assets = self.asset_server();
+        let assets = self.asset_server().clone();
         let folder_handle = assets.load_folder(path);
-        let folder_id = folder_handle.clone().id();
-        
-        let _ = self.await_event::<AssetEvent<LoadedFolder>>(|evt| {
-            match evt {
-                AssetEvent::LoadedWithDependencies { id } => { folder_id == *id },
-                _ => false
-            }
-        }).await;
+        let folder_id = folder_handle.id();
 
-        let world = self.borrow().await;
-        let folders = world.get_resource::<Assets<LoadedFolder>>().unwrap();
-
-        let folder = (*folders).get(&folder_handle.clone()).unwrap();
-        let folder = LoadedFolder {
-            handles: folder.handles.clone(),
-        };
-        (folder_handle, folder)
+        let folder = self.wait_until(move |world| {
+            if !assets.is_loaded_with_dependencies(folder_id) { return None }
+            let Some(folders) = world.get_resource::<Assets<LoadedFolder>>() else {
+                return Some(Err(FlowError::MissingResource(type_name::<Assets<LoadedFolder>>())))
+            };
+            let folder = folders.get(folder_id)?;
+            Some(Ok(LoadedFolder { handles: folder.handles.clone() }))
+        });
+        (folder_handle, unwrap_flow(folder.await))
     }
 
-    /// Wait until an event which satisfies `filter` occures before continuing
+    /// Wait until an event which satisfies `filter` occures before continuing.
+    /// Waiting doesn't borrow the [`World`], `filter` is run on the bevy side.
+    /// 
+    /// Each flow only sees an event once, starting from the first time it waits 
+    /// on that type of event, so an event that fired before then, or that an 
+    /// earlier wait already saw, won't end this wait.
     /// 
     /// **NOTE:** Be sure to use `await` on this function or it will be skipped
     ///
     /// # Panics
     /// 
     /// Panics if the the event hasn't been insterted into the bevy App.
+    /// See [`try_await_event`](Self::try_await_event)
     /// 
     /// See [`App::add_event`]
-    pub async fn await_event<E>(&self, filter: impl Fn(&E) -> bool) 
+    pub async fn await_event<E>(&self, filter: impl Fn(&E) -> bool + Send + 'static) 
     where
         E: Event
     {
-        loop {
-            let world = self.borrow().await;
-            let Some(events) = world.get_resource::<Events<E>>() else {
-                panic!("Resource {} is not present", type_name::<E>())
-            };
-            let mut reader = events.get_reader();
+        unwrap_flow(self.try_await_event(filter).await)
+    }
 
-            for evt in reader.read(events) {
-                if filter(evt) { return }
-            }
-        }
+    /// Wait until an event which satisfies `filter` occures before continuing.
+    /// See [`await_event`](Self::await_event) for which events are seen.
+    /// 
+    /// **NOTE:** Be sure to use `await` on this function or it will be skipped
+    /// 
+    /// # Errors
+    /// 
+    /// - [`FlowError::MissingEvent`] if the event hasn't been added to the app
+    /// - [`FlowError::Cancelled`] if the flow is cancelled while waiting
+    pub async fn try_await_event<E>(&self, filter: impl Fn(&E) -> bool + Send + 'static) -> Result<(), FlowError>
+    where
+        E: Event
+    {
+        self.try_await_event_return(move |evt| filter(evt).then_some(())).await
     }
     
-    /// Wait until an event which satisfies `filter` occures before returning it.
-    /// This requires the event to implement [`Clone`]
+    /// Wait until an event which satisfies `filter` occures, then return whatever
+    /// `filter` made from it. See [`await_event`](Self::await_event) for which 
+    /// events are seen.
     /// 
     /// **NOTE:** Be sure to use `await` on this function or it will be skipped
     ///
@@ -333,57 +588,413 @@ impl FlowContext {
     /// Panics if the the event hasn't been insterted into the bevy App.
     /// 
     /// See [`App::add_event`]
-    pub async fn await_event_return<E, Ret>(&self, filter: impl Fn(&E) -> Option<Ret>) -> Ret
+    pub async fn await_event_return<E, Ret>(&self, filter: impl Fn(&E) -> Option<Ret> + Send + 'static) -> Ret
     where
-        E: Event
+        E: Event,
+        Ret: Send + 'static,
     {
-        loop {
-            let world = self.borrow().await;
-            let Some(events) = world.get_resource::<Events<E>>() else {
-                panic!("Flow Context unable to locate event: {}", type_name::<E>());
-            };
-            for evt in events.get_reader().read(events) {
-                if let Some(ret) = filter(evt) {
-                    return ret
-                }
+        unwrap_flow(self.try_await_event_return(filter).await)
+    }
+
+    /// Wait until an event which satisfies `filter` occures, then return whatever
+    /// `filter` made from it. See [`await_event`](Self::await_event) for which 
+    /// events are seen.
+    /// 
+    /// **NOTE:** Be sure to use `await` on this function or it will be skipped
+    /// 
+    /// # Errors
+    /// 
+    /// - [`FlowError::MissingEvent`] if the event hasn't been added to the app
+    /// - [`FlowError::Cancelled`] if the flow is cancelled while waiting
+    pub async fn try_await_event_return<E, Ret>(
+        &self, 
+        filter: impl Fn(&E) -> Option<Ret> + Send + 'static,
+    ) -> Result<Ret, FlowError>
+    where
+        E: Event,
+        Ret: Send + 'static,
+    {
+        self.cursors.subscribe::<E>(self.now.events());
+        let cursors = self.cursors.clone();
+        self.wait_until(move |world| cursors.read_until(world, &filter).transpose()).await
+    }
+
+    /// Every event of type `E` this flow hasn't seen yet, as an async [`Stream`].
+    /// Like [`await_event`](Self::await_event), each event is only seen once, 
+    /// and the stream shares its place with the other event waiting methods.
+    /// 
+    /// ```rust,no_run
+    /// # use bevy::prelude::*;
+    /// # use bevy_flow::prelude::*;
+    /// use bevy::tasks::futures_lite::StreamExt;
+    /// 
+    /// # #[derive(Clone, Event)] struct Damaged(u32);
+    /// async fn count_damage(ctx: FlowContext) {
+    ///     let mut total = 0;
+    ///     let mut hits = ctx.events::<Damaged>();
+    ///     while let Some(Damaged(amount)) = hits.next().await {
+    ///         total += amount;
+    ///         if total > 100 { break }
+    ///     }
+    /// }
+    /// ```
+    /// 
+    /// The stream ends if the event hasn't been added to the app, or if the flow is cancelled.
+    pub fn events<E: Event + Clone>(&self) -> impl Stream<Item = E> + Unpin + '_ {
+        self.cursors.subscribe::<E>(self.now.events());
+        Box::pin(stream::unfold((), move |()| async move {
+            if let Some(evt) = self.cursors.take_unseen::<E>() {
+                return Some((evt, ()))
             }
-        }
+
+            let cursors = self.cursors.clone();
+            self.wait_until(move |world| match cursors.read_ahead::<E>(world) {
+                Ok(()) => cursors.has_unseen::<E>().then_some(Ok(())),
+                Err(err) => Some(Err(err)),
+            }).await.ok()?;
+            self.cursors.take_unseen::<E>().map(|evt| (evt, ()))
+        }))
     }
 
-    /// Delay this flow until a given state is reached.
+    /// Delay this flow until a given state is reached. Waiting doesn't borrow the [`World`]
     /// 
     /// **NOTE:** Be sure to use `await` on this function or it will be skipped
     ///
     /// # Panics
     /// 
     /// Panics if the the State hasn't been insterted into the bevy App.
+    /// See [`try_await_state`](Self::try_await_state)
     /// 
     /// See [`App::init_state`] or [`App::insert_state`]
     pub async fn await_state<S: States>(&self, matches: S) {
-        loop {
-            let world = self.borrow().await;
-            let state = world.get_resource::<State<S>>().unwrap();
-            if state.get() == &matches {
-                return
+        unwrap_flow(self.try_await_state(matches).await)
+    }
+
+    /// Delay this flow until a given state is reached. Waiting doesn't borrow the [`World`]
+    /// 
+    /// **NOTE:** Be sure to use `await` on this function or it will be skipped
+    /// 
+    /// # Errors
+    /// 
+    /// - [`FlowError::MissingState`] if the state hasn't been added to the app
+    /// - [`FlowError::Cancelled`] if the flow is cancelled while waiting
+    pub async fn try_await_state<S: States>(&self, matches: S) -> Result<(), FlowError> {
+        self.wait_until(move |world| match world.get_resource::<State<S>>() {
+            Some(state) => (state.get() == &matches).then_some(Ok(())),
+            None => Some(Err(FlowError::MissingState(type_name::<S>()))),
+        }).await
+    }
+
+    /// Wait until `filter` is happy with a [`Resource`]. Waiting doesn't borrow the [`World`]
+    /// 
+    /// **NOTE:** Be sure to use `await` on this function or it will be skipped
+    ///
+    /// # Panics
+    /// 
+    /// Panics if the resource isn't present. See [`try_await_resource`](Self::try_await_resource)
+    pub async fn await_resource<R: Resource>(&self, filter: impl Fn(&R) -> bool + Send + 'static) {
+        unwrap_flow(self.try_await_resource(filter).await)
+    }
+
+    /// Wait until `filter` is happy with a [`Resource`]. Waiting doesn't borrow the [`World`]
+    /// 
+    /// **NOTE:** Be sure to use `await` on this function or it will be skipped
+    /// 
+    /// # Errors
+    /// 
+    /// - [`FlowError::MissingResource`] if the resource isn't present
+    /// - [`FlowError::Cancelled`] if the flow is cancelled while waiting
+    pub async fn try_await_resource<R: Resource>(
+        &self, 
+        filter: impl Fn(&R) -> bool + Send + 'static,
+    ) -> Result<(), FlowError> {
+        self.wait_until(move |world| match world.get_resource::<R>() {
+            Some(resource) => filter(resource).then_some(Ok(())),
+            None => Some(Err(FlowError::MissingResource(type_name::<R>()))),
+        }).await
+    }
+
+    /// Wait until the next frame. Waiting doesn't borrow the [`World`]
+    /// 
+    /// **NOTE:** Be sure to use `await` on this function or it will be skipped
+    pub async fn next_frame(&self) {
+        self.wait_frames(1).await
+    }
+
+    /// Wait for a number of frames to pass, counting from the frame this is 
+    /// called in. Waiting doesn't borrow the [`World`]
+    /// 
+    /// **NOTE:** Be sure to use `await` on this function or it will be skipped
+    pub async fn wait_frames(&self, frames: u32) {
+        let now = self.now.clone();
+        let start = now.frame();
+        let waited = self.wait_until(move |_world| {
+            (now.frame().wrapping_sub(start) >= frames).then_some(Ok(()))
+        });
+        unwrap_flow(waited.await)
+    }
+
+    /// Wait for some time to pass on [`Time<Virtual>`](bevy::time::Virtual), 
+    /// so pausing the game also pauses this. Waiting doesn't borrow the [`World`]
+    /// 
+    /// **NOTE:** Be sure to use `await` on this function or it will be skipped
+    /// 
+    /// # Panics
+    /// 
+    /// Panics if `Time<Virtual>` isn't present. It's added by [`TimePlugin`](bevy::time::TimePlugin)
+    pub async fn sleep(&self, duration: Duration) {
+        self.sleep_with(duration, FlowClock::Virtual).await
+    }
+
+    /// Wait for some time to pass on a particular [`FlowClock`]. Waiting doesn't
+    /// borrow the [`World`]
+    /// 
+    /// **NOTE:** Be sure to use `await` on this function or it will be skipped
+    /// 
+    /// # Panics
+    /// 
+    /// Panics if the clocks [`Time`] isn't present. They're added by [`TimePlugin`](bevy::time::TimePlugin)
+    pub async fn sleep_with(&self, duration: Duration, clock: FlowClock) {
+        unwrap_flow(self.try_sleep_with(duration, clock).await)
+    }
+
+    /// Wait for some time to pass on a particular [`FlowClock`].
+    /// 
+    /// # Errors
+    /// 
+    /// - [`FlowError::MissingResource`] if the clocks [`Time`] isn't present
+    /// - [`FlowError::Cancelled`] if the flow is cancelled while sleeping
+    pub async fn try_sleep_with(&self, duration: Duration, clock: FlowClock) -> Result<(), FlowError> {
+        // flows started before the waiters were first checked start on the first check
+        let mut start = self.now.elapsed(clock);
+        self.wait_until(move |world| {
+            let Some(now) = clock.elapsed(world) else {
+                return Some(Err(FlowError::MissingResource(clock.type_name())))
+            };
+            let start = *start.get_or_insert(now);
+            (now - start >= duration).then_some(Ok(()))
+        }).await
+    }
+
+    /// Wait on several things at once, like an event, a state change, or a timeout,
+    /// and find out which one happened first. See [`FlowSelect`]
+    pub fn select<'a, T: 'a>(&'a self) -> FlowSelect<'a, impl Future<Output = Result<T, FlowError>> + 'a> {
+        FlowSelect::new(self, pending())
+    }
+
+    /// Wait on several futures of the same type at once. Returns the index of 
+    /// the one that finished first, along with its output. The rest are dropped.
+    /// 
+    /// If `futures` is empty, this never finishes.
+    /// 
+    /// **NOTE:** Be sure to use `await` on this function or it will be skipped
+    pub async fn race<F: Future>(&self, futures: impl IntoIterator<Item = F>) -> (usize, F::Output) {
+        let mut futures = futures.into_iter()
+            .map(Box::pin)
+            .collect::<Vec<_>>();
+
+        poll_fn(|cx| {
+            for (index, future) in futures.iter_mut().enumerate() {
+                if let Poll::Ready(out) = future.as_mut().poll(cx) {
+                    return Poll::Ready((index, out))
+                }
             }
+            Poll::Pending
+        }).await
+    }
+
+    /// Wait until a certain condition is met before continuing, and return 
+    /// whatever `cond` made. Waiting doesn't borrow the [`World`], `cond`
+    /// only gets to look at it.
+    /// 
+    /// **NOTE:** Be sure to use `await` on this function or it will be skipped
+    pub async fn await_cond<R: Send + 'static>(
+        &self, 
+        mut cond: impl FnMut(&World) -> Option<R> + Send + 'static,
+    ) -> R {
+        unwrap_flow(self.wait_until(move |world| cond(world).map(Ok)).await)
+    }
+
+    /// Start a [`Flow`] as a child of this one. The child runs alongside its 
+    /// parent, and can be `.await`ed through the returned [`FlowTaskHandle`].
+    /// 
+    /// Children are cancelled when their parent is cancelled, or when it 
+    /// finishes while they're still running. They're started the next time 
+    /// the plugin lends the [`World`] to flows, and use the parent's schedule.
+    /// 
+    /// ```rust
+    /// # use bevy::prelude::*;
+    /// # use bevy_flow::prelude::*;
+    /// # fn bake_lighting() -> u32 { 42 }
+    /// async fn build_level(ctx: FlowContext) {
+    ///     let lighting = ctx.spawn_child(|_ctx: FlowContext| async move {
+    ///         bake_lighting()
+    ///     });
+    ///     // ...
+    ///     let lighting = lighting.await.unwrap();
+    /// }
+    /// ```
+    pub fn spawn_child<F>(&self, flow: F) -> FlowTaskHandle<F::Output>
+    where
+        F: Flow<Input = ()>,
+    {
+        let id = FlowTaskId::next();
+        let control = Arc::new(FlowControl::pinned_to(self.control.schedule()));
+        self.control.adopt(&control);
+
+        let (handle, result) = FlowTaskHandle::new(id, control.clone());
+        self.commands().add(move |world: &mut World| {
+            start_child(world, id, flow, (), result, control);
+        });
+        handle
+    }
+
+    /// Run each of `flows` as a child flow at the same time, and wait for all
+    /// of them to finish. Returns their outputs, in the same order as `flows`.
+    /// See [`spawn_child`](Self::spawn_child)
+    /// 
+    /// If a child panics, so does this flow.
+    /// 
+    /// **NOTE:** Be sure to use `await` on this function or it will be skipped
+    /// 
+    /// ```rust
+    /// # use bevy::prelude::*;
+    /// # use bevy_flow::prelude::*;
+    /// # fn generate_chunk(index: u32) -> Vec<u8> { vec![] }
+    /// async fn generate_world(ctx: FlowContext) {
+    ///     let chunks = ctx.join_all((0..4).map(|index| move |_ctx: FlowContext| async move {
+    ///         generate_chunk(index)
+    ///     })).await;
+    ///     // ...
+    /// }
+    /// ```
+    pub async fn join_all<F>(&self, flows: impl IntoIterator<Item = F>) -> Vec<F::Output>
+    where
+        F: Flow<Input = ()>,
+    {
+        let children = flows.into_iter()
+            .map(|flow| self.spawn_child(flow))
+            .collect::<Vec<_>>();
+
+        let mut outputs = Vec::with_capacity(children.len());
+        for child in children {
+            outputs.push(unwrap_flow(child.await));
         }
+        outputs
     }
 
-    /// Wait until a certain condition is met before continuing
+    /// Run each of `flows` as a child flow at the same time, and wait for all
+    /// of them to succeed. Returns their outputs, in the same order as `flows`.
+    /// 
+    /// As soon as one of them returns an error, panics, or is cancelled, the 
+    /// rest are cancelled, and that error is returned. Errors are turned into 
+    /// [`FlowError::Failed`], unless they already are a [`FlowError`].
     /// 
     /// **NOTE:** Be sure to use `await` on this function or it will be skipped
-    pub async fn await_cond<R>(&self, cond: impl Fn(&WorldRef) -> Option<R>) -> R {
-        loop {
-            let world = self.borrow().await;
-            if let Some(ret) = cond(&world) {
-                return ret
+    /// 
+    /// ```rust
+    /// # use bevy::prelude::*;
+    /// # use bevy_flow::prelude::*;
+    /// # struct Level;
+    /// # fn read_level(name: &str) -> Result<Level, String> { Ok(Level) }
+    /// async fn load_campaign(ctx: FlowContext) -> Result<(), FlowError> {
+    ///     let names = ["forest", "cave", "castle"];
+    ///     let levels = ctx.try_join_all(names.map(|name| move |_ctx: FlowContext| async move {
+    ///         read_level(name)
+    ///     })).await?;
+    ///     // ...
+    ///     Ok(())
+    /// }
+    /// ```
+    pub async fn try_join_all<F, T, E>(&self, flows: impl IntoIterator<Item = F>) -> Result<Vec<T>, FlowError>
+    where
+        F: Flow<Input = (), Output = Result<T, E>>,
+        T: Send + 'static,
+        E: Display + Send + 'static,
+    {
+        let children = flows.into_iter()
+            .map(|flow| self.spawn_child(flow))
+            .collect::<Vec<_>>();
+        let mut waiting = children.iter()
+            .map(|child| Some(child.clone().into_future()))
+            .collect::<Vec<_>>();
+        let mut outputs = children.iter().map(|_| None).collect::<Vec<_>>();
+
+        let failed = poll_fn(|cx| {
+            for (index, child) in waiting.iter_mut().enumerate() {
+                let Some(future) = child else { continue };
+                let Poll::Ready(result) = future.as_mut().poll(cx) else { continue };
+                *child = None;
+
+                match result {
+                    Ok(Ok(output)) => outputs[index] = Some(output),
+                    Ok(Err(err)) => return Poll::Ready(Some(FlowError::from_failure(&err))),
+                    Err(err) => return Poll::Ready(Some(err)),
+                }
+            }
+            match waiting.iter().all(Option::is_none) {
+                true => Poll::Ready(None),
+                false => Poll::Pending,
             }
+        }).await;
+
+        if let Some(err) = failed {
+            // children that already finished keep their cancel hooks to themselves
+            for (child, waiting) in children.iter().zip(&waiting) {
+                if waiting.is_some() {
+                    child.cancel();
+                }
+            }
+            return Err(err)
         }
+        Ok(outputs.into_iter().flatten().collect())
+    }
+}
+
+
+
+
+
+/// Unwrap the result of a `try_*` method. Cancellation, and losing the app,
+/// stop the flow, everything else panics
+fn unwrap_flow<T>(result: Result<T, FlowError>) -> T {
+    match result {
+        Ok(value) => value,
+        // there's nothing left to report to once the app is gone
+        Err(err @ (FlowError::Cancelled | FlowError::Disconnected)) => stop_flow(err),
+        Err(err) => panic!("{err}"),
     }
 }
 
+/// Stop the flow by unwinding out of it. The unwind is caught where the flow
+/// was started, and the error is handed to its [`FlowTaskHandle`](crate::handle::FlowTaskHandle).
+/// 
+/// [`resume_unwind`] is used so the panic hook isn't triggered
+fn stop_flow(err: FlowError) -> ! {
+    resume_unwind(Box::new(err))
+}
+
 
 
+/// Withdraws a request for the [`World`] if it's dropped before being answered
+struct PendingRequest<'a> {
+    send: &'a Sender<LTResult>,
+    control: &'a FlowControl,
+    answered: bool,
+}
+
+impl<'a> Drop for PendingRequest<'a> {
+    fn drop(&mut self) {
+        if self.answered { return }
+        // this mustn't block, as the runner only reads the channel while it's
+        // lending to this flow, which the loan budget can put off for a while
+        if let Err(TrySendError::Full(_)) = self.send.try_send(LTResult::Withdrawn) {
+            self.control.withdraw();
+            let _ = self.send.try_send(LTResult::Nudge);
+        }
+    }
+}
+
 
 
 /// Temporary access to bevy's [`World`] across threads safely.
@@ -397,9 +1008,14 @@ pub struct WorldRef<'a> {
 
 impl<'a> Drop for WorldRef<'a> {
     fn drop(&mut self) {
-        block_on({
+        // events sent while the flow had the `World` shouldn't be seen by 
+        // anything it subscribes to afterwards
+        self.linker.now.update(self.world);
+        // this may run while the flow is unwinding, so it mustn't panic. If
+        // the runner is gone, there is no one to give the `World` back to anyway
+        let _ = block_on({
             self.linker.send.send(LTResult::DoneWithWorld)
-        }).unwrap();
+        });
     }
 }
 
@@ -417,47 +1033,84 @@ impl<'a> DerefMut for WorldRef<'a> {
 }
 
 impl<'a> WorldRef<'a> {
-    // /// Schedules changing a [`State`] resource at the end of the next update cycle.
-    // /// 
-    // /// This is equivalent to calling [`NextState::set`] in a normal system
-    // /// 
-    // /// # Panics
-    // /// 
-    // /// Panics if the the State hasn't been insterted into the bevy App.
-    // /// 
-    // /// See [`App::init_state`] or [`App::insert_state`]
-    // pub fn set_state<S: States>(&mut self, new: S) {
-    //     println!("Setting State {new:?}");
-    //     let mut next = self.world.get_resource_mut::<NextState<S>>().unwrap();
-    //     next.set(new);
-    // }
-
-    // /// Sends an [`Event`] to the game, that will be recieved on the next update cycle.
-    // /// 
-    // /// This is the same as calling [`EventWriter::send`] in a normal system
-    // /// 
-    // /// # Panics
-    // /// 
-    // /// Panics if the the event hasn't been insterted into the bevy App.
-    // /// 
-    // /// See [`App::add_event`]
-    // pub fn send_event<E: Event>(&mut self, event: E) -> EventId<E> {
-    //     println!("Sending Event");
-    //     let mut events = self.world.get_resource_mut::<Events<E>>().unwrap();
-    //     events.send(event)
-    // }
-
-    // /// Get the current state
-    // /// 
-    // /// # Panics
-    // /// 
-    // /// Panics if the State is not present
-    // /// 
-    // /// See [`App::init_state`] or [`App::insert_state`]
-    // pub fn get_state<S: States>(&self) -> S {
-    //     let next = self.world.get_resource::<State<S>>().unwrap();
-    //     next.get().clone()
-    // }
+    /// Gets a copy of a [`Resource`]
+    /// 
+    /// # Panics
+    /// 
+    /// Panics if the Resource doesn't exist. See [`try_copy_resource`](Self::try_copy_resource)
+    pub fn copy_resource<R: Resource + Clone>(&self) -> R {
+        unwrap_flow(self.try_copy_resource())
+    }
+
+    /// Gets a copy of a [`Resource`]
+    /// 
+    /// # Errors
+    /// 
+    /// Returns [`FlowError::MissingResource`] if the resource doesn't exist
+    pub fn try_copy_resource<R: Resource + Clone>(&self) -> Result<R, FlowError> {
+        self.world.get_resource::<R>()
+            .cloned()
+            .ok_or(FlowError::MissingResource(type_name::<R>()))
+    }
+
+    /// Schedules changing a [`State`] resource at the end of the next update cycle.
+    /// 
+    /// This is equivalent to calling [`NextState::set`] in a normal system
+    /// 
+    /// If the state is not present in the app it is added.
+    pub fn set_state<S: States + FreelyMutableState>(&mut self, new: S) {
+        if let Some(mut next) = self.world.get_resource_mut::<NextState<S>>() {
+            next.set(new);
+        }
+        else {
+            self.world.insert_resource(State::new(new));
+            self.world.insert_resource(NextState::<S>::default())
+        }
+    }
+
+    /// Sends an [`Event`] to the game, that will be recieved on the next update cycle.
+    /// 
+    /// This is the same as calling [`EventWriter::send`] in a normal system
+    /// 
+    /// # Panics
+    /// 
+    /// Panics if the the event hasn't been insterted into the bevy App.
+    /// See [`try_send_event`](Self::try_send_event)
+    pub fn send_event<E: Event>(&mut self, event: E) -> EventId<E> {
+        unwrap_flow(self.try_send_event(event))
+    }
+
+    /// Sends an [`Event`] to the game, that will be recieved on the next update cycle.
+    /// 
+    /// # Errors
+    /// 
+    /// Returns [`FlowError::MissingEvent`] if the event hasn't been added to the app
+    pub fn try_send_event<E: Event>(&mut self, event: E) -> Result<EventId<E>, FlowError> {
+        let mut events = self.world.get_resource_mut::<Events<E>>()
+            .ok_or(FlowError::MissingEvent(type_name::<E>()))?;
+        Ok(events.send(event))
+    }
+
+    /// Get the current state
+    /// 
+    /// # Panics
+    /// 
+    /// Panics if the the State hasn't been insterted into the bevy App.
+    /// See [`try_get_state`](Self::try_get_state)
+    pub fn get_state<S: States>(&self) -> S {
+        unwrap_flow(self.try_get_state())
+    }
+
+    /// Get the current state
+    /// 
+    /// # Errors
+    /// 
+    /// Returns [`FlowError::MissingState`] if the state hasn't been added to the app
+    pub fn try_get_state<S: States>(&self) -> Result<S, FlowError> {
+        self.world.get_resource::<State<S>>()
+            .map(|state| state.get().clone())
+            .ok_or(FlowError::MissingState(type_name::<S>()))
+    }
 }
 
 
@@ -502,3 +1155,155 @@ impl<'a> WorldRef<'a> {
 // }
 
 
+
+
+
+#[cfg(test)]
+mod tests {
+    use std::{thread::sleep, time::Duration};
+
+    use bevy::{core::FrameCount, prelude::*};
+
+    use crate::prelude::*;
+
+    #[derive(Resource, Default)]
+    struct Loans(Vec<u32>);
+
+    #[test]
+    fn next_frame_resumes_in_the_next_frame() {
+        let mut app = App::new();
+        app.add_plugins((MinimalPlugins, FlowTasksPlugin::default()))
+            .init_resource::<Loans>()
+            // local flows are polled right before the loans, so they always
+            // ask for the `World` in time, unlike flows on other threads
+            .add_systems(Startup, |mut flows: FlowTaskManager| {
+                flows.start_local(|ctx: FlowContext| async move {
+                    for _ in 0..3 {
+                        ctx.with_world(|world| {
+                            let frame = world.resource::<FrameCount>().0;
+                            world.resource_mut::<Loans>().0.push(frame);
+                        }).await;
+                        ctx.next_frame().await;
+                    }
+                });
+            });
+
+        for _ in 0..10 {
+            app.update();
+        }
+
+        let loans = &app.world().resource::<Loans>().0;
+        assert_eq!(loans.len(), 3);
+        assert_eq!(loans[1] - loans[0], 1);
+        assert_eq!(loans[2] - loans[1], 1);
+    }
+
+    #[derive(Event)]
+    struct Ping;
+
+    #[derive(Resource, Default)]
+    struct Pings(u32);
+
+    /// Counts every [`Ping`] it sees in [`Pings`]
+    async fn count_pings(ctx: FlowContext) {
+        loop {
+            ctx.await_event::<Ping>(|_| true).await;
+            ctx.with_world(|world| world.resource_mut::<Pings>().0 += 1).await;
+        }
+    }
+
+    /// An app with a local [`count_pings`] flow, started in [`Startup`]
+    fn ping_counter() -> App {
+        let mut app = App::new();
+        app.add_plugins((MinimalPlugins, FlowTasksPlugin::default()))
+            .add_event::<Ping>()
+            .init_resource::<Pings>()
+            .add_systems(Startup, |mut flows: FlowTaskManager| {
+                flows.start_local(count_pings);
+            });
+        app
+    }
+
+    #[test]
+    fn events_sent_after_subscribing_are_seen() {
+        let mut app = ping_counter();
+        // the flow subscribes during the first update, and this is sent later that frame
+        app.add_systems(PostUpdate, |mut pings: EventWriter<Ping>, mut sent: Local<bool>| {
+            if !*sent { pings.send(Ping); }
+            *sent = true;
+        });
+
+        for _ in 0..5 {
+            app.update();
+        }
+        assert_eq!(app.world().resource::<Pings>().0, 1);
+    }
+
+    #[test]
+    fn events_sent_before_subscribing_are_not_seen() {
+        let mut app = ping_counter();
+        app.add_systems(Startup, |mut pings: EventWriter<Ping>| { pings.send(Ping); });
+
+        for _ in 0..5 {
+            app.update();
+        }
+        assert_eq!(app.world().resource::<Pings>().0, 0);
+    }
+
+    #[test]
+    fn later_flows_start_from_the_counted_events() {
+        let mut app = ping_counter();
+        // by the third frame the first flow has been counting pings, so the
+        // second flow starts from that count rather than from the oldest ping
+        let start_second = |mut flows: FlowTaskManager, frame: Res<FrameCount>| {
+            if frame.0 == 2 { flows.start_local(count_pings); }
+        };
+        app.add_systems(Update, start_second.before(FlowTaskSystemSet));
+        app.add_systems(PostUpdate, |mut pings: EventWriter<Ping>, frame: Res<FrameCount>| {
+            if frame.0 == 1 || frame.0 == 2 { pings.send(Ping); }
+        });
+
+        for _ in 0..6 {
+            app.update();
+        }
+        // two for the first flow, one for the second
+        assert_eq!(app.world().resource::<Pings>().0, 3);
+    }
+
+    #[derive(Resource, Clone, Default)]
+    struct Setting;
+
+    #[test]
+    fn withdrawing_requests_doesnt_block_the_pool() {
+        let mut app = App::new();
+        let plugin = FlowTasksPlugin::default()
+            .with_executor(FlowExecutor::DedicatedPool { threads: 1 })
+            .with_loan_budget(Duration::ZERO)
+            .with_channel_capacity(1);
+        app.add_plugins((MinimalPlugins, plugin))
+            .init_resource::<Setting>()
+            .init_resource::<Loans>()
+            .add_systems(Startup, |mut flows: FlowTaskManager| {
+                // never lent the `World` with no budget, so its requests keep timing 
+                // out, with the channel to its runner already full of its request
+                flows.start::<_, _, ()>(|ctx: FlowContext| async move {
+                    loop {
+                        let _ = ctx.copy_resource::<Setting>().with_timeout(&ctx, Duration::from_millis(1)).await;
+                    }
+                });
+                flows.start_with_priority::<_, _, ()>(FlowPriority::Critical, |ctx: FlowContext| async move {
+                    loop {
+                        ctx.with_world(|world| world.resource_mut::<Loans>().0.push(0)).await;
+                        ctx.next_frame().await;
+                    }
+                });
+            });
+
+        for _ in 0..100 {
+            app.update();
+            sleep(Duration::from_millis(1));
+        }
+        // it would stop at a handful of loans if the pools only thread was stuck
+        assert!(app.world().resource::<Loans>().0.len() > 30);
+    }
+}
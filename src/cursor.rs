@@ -0,0 +1,214 @@
+//! Where each flow keeps track of which events it has already seen
+
+use std::{any::{type_name, Any, TypeId}, collections::{HashMap, VecDeque}, sync::{Arc, Mutex, MutexGuard}};
+
+use bevy::{ecs::{component::Tick, event::ManualEventReader}, prelude::*};
+
+use crate::prelude::FlowError;
+
+
+/// A flow's place in each type of event it reads.
+/// 
+/// A cursor is created when the flow first subscribes to an event type, by
+/// waiting on it or asking for a stream of it, and is kept for the rest of 
+/// the flow. It starts from the [`EventCounts`] at that point, so the flow 
+/// sees every event sent after it subscribed exactly once, no matter how 
+/// many times it waits on that event type, or how many frames pass between 
+/// waits. Events are only kept for two frames though, so the flow needs to 
+/// read them at least that often.
+#[derive(Clone, Default)]
+pub(crate) struct EventCursors {
+    cursors: Arc<Mutex<HashMap<TypeId, Box<dyn Any + Send + Sync>>>>,
+}
+
+/// The cursor for a single type of event
+struct EventCursor<E: Event> {
+    /// Where the cursor starts, until it's first read from
+    start: Option<CursorStart>,
+    reader: ManualEventReader<E>,
+    /// Events that have been read ahead of time, but not seen by the flow yet
+    unseen: VecDeque<E>,
+}
+
+/// Where a new [`EventCursor`] starts reading from
+#[derive(Clone, Copy)]
+enum CursorStart {
+    /// After this many events of the type had been sent
+    After(usize),
+    /// Nothing was keeping count of the event type yet. If no events of that
+    /// type were sent since this tick, the cursor starts after all of them, 
+    /// otherwise at the oldest event still around, since seeing an event 
+    /// twice is better than never seeing it.
+    Since(Tick),
+}
+
+impl EventCursors {
+    /// Creates the cursor for events of type `E`, if the flow doesn't have one yet
+    pub(crate) fn subscribe<E: Event>(&self, counts: &EventCounts) {
+        self.lock().entry(TypeId::of::<E>()).or_insert_with(|| Box::new(EventCursor::<E> {
+            start: Some(counts.start::<E>()),
+            reader: default(),
+            unseen: default(),
+        }));
+    }
+
+    /// Reads unseen events of type `E`, until `read` returns something. 
+    /// Every event passed to `read` is marked as seen, even if it returns [`None`].
+    /// 
+    /// # Errors
+    /// 
+    /// [`FlowError::MissingEvent`] if the event hasn't been added to the app
+    pub(crate) fn read_until<E: Event, R>(
+        &self, 
+        world: &World, 
+        mut read: impl FnMut(&E) -> Option<R>,
+    ) -> Result<Option<R>, FlowError> {
+        let events = Self::events::<E>(world)?;
+        let mut cursors = self.lock();
+        let cursor = Self::cursor(&mut cursors, world, &events);
+
+        while let Some(evt) = cursor.unseen.pop_front() {
+            if let Some(ret) = read(&evt) { return Ok(Some(ret)) }
+        }
+        Ok(cursor.reader.read(&events).find_map(read))
+    }
+
+    /// Reads every new event of type `E` ahead of time, so they can be taken
+    /// one at a time with [`take_unseen`](Self::take_unseen)
+    /// 
+    /// # Errors
+    /// 
+    /// [`FlowError::MissingEvent`] if the event hasn't been added to the app
+    pub(crate) fn read_ahead<E: Event + Clone>(&self, world: &World) -> Result<(), FlowError> {
+        let events = Self::events::<E>(world)?;
+        let mut cursors = self.lock();
+        let cursor = Self::cursor(&mut cursors, world, &events);
+
+        cursor.unseen.extend(cursor.reader.read(&events).cloned());
+        Ok(())
+    }
+
+    /// Takes the next event that was read ahead of time, which doesn't need the [`World`]
+    pub(crate) fn take_unseen<E: Event>(&self) -> Option<E> {
+        self.lock()
+            .get_mut(&TypeId::of::<E>())?
+            .downcast_mut::<EventCursor<E>>()?
+            .unseen
+            .pop_front()
+    }
+
+    /// If there are events that were read ahead of time, but haven't been taken yet
+    pub(crate) fn has_unseen<E: Event>(&self) -> bool {
+        self.lock()
+            .get(&TypeId::of::<E>())
+            .and_then(|cursor| cursor.downcast_ref::<EventCursor<E>>())
+            .is_some_and(|cursor| !cursor.unseen.is_empty())
+    }
+
+    fn lock(&self) -> MutexGuard<'_, HashMap<TypeId, Box<dyn Any + Send + Sync>>> {
+        // a panic while reading can't leave a cursor half updated, so poisoning is fine to ignore
+        self.cursors.lock().unwrap_or_else(|poison| poison.into_inner())
+    }
+
+    fn events<E: Event>(world: &World) -> Result<Res<'_, Events<E>>, FlowError> {
+        world.get_resource_ref::<Events<E>>()
+            .ok_or(FlowError::MissingEvent(type_name::<E>()))
+    }
+
+    fn cursor<'a, E: Event>(
+        cursors: &'a mut HashMap<TypeId, Box<dyn Any + Send + Sync>>, 
+        world: &World,
+        events: &Res<Events<E>>,
+    ) -> &'a mut EventCursor<E> {
+        // waits always subscribe first, so this only starts from the current 
+        // events if something read without subscribing
+        let cursor: &mut EventCursor<E> = cursors.entry(TypeId::of::<E>())
+            .or_insert_with(|| Box::new(EventCursor { 
+                start: None,
+                reader: events.get_reader_current(), 
+                unseen: VecDeque::<E>::new(),
+            }))
+            .downcast_mut()
+            .expect("event cursors are stored by the TypeId of their event");
+
+        let count = match cursor.start.take() {
+            None => return cursor,
+            Some(CursorStart::After(count)) => count,
+            Some(CursorStart::Since(tick)) => {
+                // events sent during the same loan share its tick, so that counts too
+                let since = Tick::new(tick.get().wrapping_sub(1));
+                match events.last_changed().is_newer_than(since, world.read_change_tick()) {
+                    true => events.oldest_event_count(),
+                    false => event_count(&**events),
+                }
+            }
+        };
+        // skip the events that were sent before the cursor started
+        let skip = count.saturating_sub(events.oldest_event_count());
+        cursor.reader.read(events).take(skip).for_each(drop);
+        cursor
+    }
+}
+
+
+/// How many events of each type had been sent, as of the last time they 
+/// were counted. 
+/// 
+/// Flows can't look at [`Events`] without borrowing the [`World`], so new 
+/// [`EventCursors`] start from these. They're counted whenever the waiters 
+/// are checked, and at the end of every loan, but only for the event types 
+/// flows have subscribed to.
+#[derive(Default)]
+pub(crate) struct EventCounts {
+    counts: Mutex<Counts>,
+}
+
+#[derive(Default)]
+struct Counts {
+    /// The world's change tick when the events were counted
+    tick: Tick,
+    sent: HashMap<TypeId, SentEvents>,
+}
+
+struct SentEvents {
+    count: fn(&World) -> Option<usize>,
+    /// [`None`] until the event type is first counted
+    sent: Option<usize>,
+}
+
+impl EventCounts {
+    /// Counts every type of event flows have subscribed to
+    pub(crate) fn update(&self, world: &World) {
+        let mut counts = self.lock();
+        counts.tick = world.read_change_tick();
+        for sent in counts.sent.values_mut() {
+            sent.sent = (sent.count)(world);
+        }
+    }
+
+    /// Where a cursor for `E` that's being created now should start, 
+    /// keeping count of `E` from now on if nothing was yet
+    fn start<E: Event>(&self) -> CursorStart {
+        let mut counts = self.lock();
+        let tick = counts.tick;
+        let sent = counts.sent.entry(TypeId::of::<E>()).or_insert(SentEvents { 
+            count: |world| world.get_resource::<Events<E>>().map(event_count), 
+            sent: None,
+        });
+        match sent.sent {
+            Some(count) => CursorStart::After(count),
+            None => CursorStart::Since(tick),
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Counts> {
+        // counting can't panic halfway, so poisoning is fine to ignore
+        self.counts.lock().unwrap_or_else(|poison| poison.into_inner())
+    }
+}
+
+/// How many events of type `E` have ever been sent
+fn event_count<E: Event>(events: &Events<E>) -> usize {
+    // the two buffers hold consecutive events, so this is one past the newest
+    events.oldest_event_count() + events.len()
+}
@@ -0,0 +1,117 @@
+//! Flows that belong to an entity, and are cancelled along with it
+
+use std::sync::Arc;
+
+use bevy::{
+    ecs::{component::{ComponentHooks, ComponentId, StorageType}, system::EntityCommands, world::DeferredWorld},
+    prelude::*,
+};
+
+use crate::{
+    error::FlowOutcome,
+    flow::Flow,
+    plugin::{start_in_world, FlowOptions},
+    runner::{FlowControl, FlowTaskId},
+};
+
+
+/// The flows that belong to an entity. Added by [`EntityFlowExt::start_flow`].
+///
+/// When the entity is despawned, or this component is removed, its flows are
+/// cancelled, and their [`on_cancel`](crate::context::FlowContext::on_cancel)
+/// hooks run.
+pub struct FlowOwner {
+    flows: Vec<(FlowTaskId, Arc<FlowControl>)>,
+}
+
+impl FlowOwner {
+    /// The flows owned by this entity. This can include flows that have
+    /// finished recently
+    pub fn flows(&self) -> impl Iterator<Item = FlowTaskId> + '_ {
+        self.flows.iter().map(|(id, _)| *id)
+    }
+
+    fn add(&mut self, id: FlowTaskId, control: Arc<FlowControl>) {
+        self.flows.retain(|(_, control)| !control.is_finished());
+        self.flows.push((id, control));
+    }
+
+    fn cancel_all(world: DeferredWorld, entity: Entity, _component: ComponentId) {
+        let Some(owner) = world.get::<FlowOwner>(entity) else { return };
+        for (id, control) in &owner.flows {
+            debug!("Cancelling flow {id:?}, as {entity:?} no longer owns it");
+            control.cancel();
+        }
+    }
+}
+
+impl Component for FlowOwner {
+    const STORAGE_TYPE: StorageType = StorageType::Table;
+
+    fn register_component_hooks(hooks: &mut ComponentHooks) {
+        hooks.on_remove(Self::cancel_all);
+    }
+}
+
+/// Adds methods for starting flows that belong to an entity to [`EntityCommands`]
+///
+/// ```rust
+/// # use bevy::prelude::*;
+/// # use bevy_flow::prelude::*;
+/// # #[derive(Component)] struct Door;
+/// # #[derive(Component)] struct Open;
+/// fn open_doors(doors: Query<Entity, Added<Door>>, mut commands: Commands) {
+///     for door in &doors {
+///         commands.entity(door).start_flow(|ctx: FlowContext| async move {
+///             let door = ctx.entity().unwrap();
+///             ctx.sleep(std::time::Duration::from_secs(2)).await;
+///             // stops here if the door was despawned in the meantime
+///             ctx.with_world(|world| { world.entity_mut(door).insert(Open); }).await;
+///         });
+///     }
+/// }
+/// ```
+pub trait EntityFlowExt {
+    /// Start a [`Flow`] that belongs to this entity, and doesn't need any
+    /// [`Input`](Flow::Input). See [`FlowOwner`]
+    fn start_flow<F>(&mut self, flow: F) -> FlowTaskId
+    where
+        F: Flow<Input = ()>,
+        F::Output: FlowOutcome,
+    {
+        self.start_flow_with(flow, ())
+    }
+
+    /// Start a [`Flow`] that belongs to this entity, with its [`Input`](Flow::Input).
+    /// See [`FlowOwner`]
+    fn start_flow_with<F>(&mut self, flow: F, input: F::Input) -> FlowTaskId
+    where
+        F: Flow,
+        F::Output: FlowOutcome;
+}
+
+impl EntityFlowExt for EntityCommands<'_> {
+    fn start_flow_with<F>(&mut self, flow: F, input: F::Input) -> FlowTaskId
+    where
+        F: Flow,
+        F::Output: FlowOutcome,
+    {
+        let id = FlowTaskId::next();
+        self.add(move |entity: Entity, world: &mut World| {
+            if world.get_entity(entity).is_none() {
+                warn!("Flow {} wasn't started, because {entity:?} doesn't exist", flow.name());
+                return
+            }
+
+            let options = FlowOptions { owner: Some(entity), ..default() };
+            let control = start_in_world(world, id, flow, input, options).control().clone();
+            match world.get_mut::<FlowOwner>(entity) {
+                Some(mut owner) => owner.add(id, control),
+                None => {
+                    world.entity_mut(entity).insert(FlowOwner { flows: vec![(id, control)] });
+                },
+            }
+        });
+        id
+    }
+}
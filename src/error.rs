@@ -0,0 +1,114 @@
+//! Errors that flows can run into
+
+use std::{any::Any, error::Error, fmt::{Display, Formatter, Result as FmtResult}};
+
+
+/// The reasons a flow, or something a flow asked for, couldn't be completed
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum FlowError {
+    /// The flow was cancelled by [`FlowTaskManager::stop`](crate::plugin::FlowTaskManager::stop)
+    /// or [`FlowTaskHandle::cancel`](crate::handle::FlowTaskHandle::cancel)
+    Cancelled,
+    /// The flow stopped without producing a result, or the result was already
+    /// taken through another [`FlowTaskHandle`](crate::handle::FlowTaskHandle)
+    NoResult,
+    /// The runner driving the flow was dropped, so the [`World`](bevy::prelude::World)
+    /// can't be reached anymore
+    Disconnected,
+    /// A [`Resource`](bevy::prelude::Resource) isn't present. Holds the type name
+    MissingResource(&'static str),
+    /// A [`State`](bevy::prelude::State) isn't present. Holds the type name
+    MissingState(&'static str),
+    /// An [`Event`](bevy::prelude::Event) hasn't been added to the app. Holds the type name
+    MissingEvent(&'static str),
+    /// The [`AssetPlugin`](bevy::prelude::AssetPlugin) isn't available
+    NoAssetServer,
+    /// The flow returned an error. Holds the errors message
+    Failed(String),
+    /// The flow panicked. Holds the panic message
+    Panicked(String),
+    /// Waiting took longer than the timeout allowed.
+    /// See [`FlowFutureExt::with_timeout`](crate::select::FlowFutureExt::with_timeout)
+    Timeout,
+    /// No flow has been registered with the name. 
+    /// See [`FlowAppExt::register_flow`](crate::app::FlowAppExt::register_flow)
+    NotRegistered(String),
+    /// A registered flow was started with the wrong input. Holds the type name 
+    /// of the input it takes
+    WrongInput(&'static str),
+}
+
+impl Display for FlowError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        match self {
+            Self::Cancelled => write!(f, "the flow was cancelled"),
+            Self::NoResult => write!(f, "the flow stopped without a result"),
+            Self::Disconnected => write!(f, "the flow lost its connection to the app"),
+            Self::MissingResource(name) => write!(f, "resource {name} is not present"),
+            Self::MissingState(name) => write!(f, "state {name} is not present"),
+            Self::MissingEvent(name) => write!(f, "event {name} has not been added to the app"),
+            Self::NoAssetServer => write!(f, "the AssetServer is not available"),
+            Self::Failed(msg) => write!(f, "{msg}"),
+            Self::Panicked(msg) => write!(f, "the flow panicked: {msg}"),
+            Self::Timeout => write!(f, "timed out while waiting"),
+            Self::NotRegistered(name) => write!(f, "no flow named {name} has been registered"),
+            Self::WrongInput(name) => write!(f, "the flow takes an input of type {name}"),
+        }
+    }
+}
+
+impl Error for FlowError { }
+
+impl FlowError {
+    /// Turn an error a flow returned into a [`FlowError`]. `FlowError`s are
+    /// kept as they are, instead of being flattened into a message
+    pub(crate) fn from_failure<E: Display + 'static>(err: &E) -> Self {
+        match (err as &dyn Any).downcast_ref::<FlowError>() {
+            Some(err) => err.clone(),
+            None => Self::Failed(err.to_string()),
+        }
+    }
+
+    /// Turn the payload of a caught panic into a [`FlowError`]. Flows stopped by
+    /// cancellation unwind with a `FlowError`, which is passed through as is.
+    pub(crate) fn from_panic(payload: Box<dyn Any + Send>) -> Self {
+        let payload = match payload.downcast::<FlowError>() {
+            Ok(err) => return *err,
+            Err(payload) => payload,
+        };
+
+        if let Some(msg) = payload.downcast_ref::<&str>() {
+            Self::Panicked(msg.to_string())
+        }
+        else if let Some(msg) = payload.downcast_ref::<String>() {
+            Self::Panicked(msg.clone())
+        }
+        else {
+            Self::Panicked("Box<dyn Any>".to_string())
+        }
+    }
+}
+
+
+/// What a flow started by [`FlowTaskManager::start`](crate::plugin::FlowTaskManager::start)
+/// can return. This is `()` for flows that can't fail, and `Result<(), E>` 
+/// for flows that can.
+/// 
+/// When a flow returns an error, a [`FlowFailed`](crate::plugin::FlowFailed)
+/// event is sent.
+pub trait FlowOutcome: Send + 'static {
+    /// The reason the flow failed, or [`None`] if it succeeded
+    fn flow_error(&self) -> Option<FlowError>;
+}
+
+impl FlowOutcome for () {
+    fn flow_error(&self) -> Option<FlowError> {
+        None
+    }
+}
+
+impl<E: Display + Send + 'static> FlowOutcome for Result<(), E> {
+    fn flow_error(&self) -> Option<FlowError> {
+        self.as_ref().err().map(FlowError::from_failure)
+    }
+}
@@ -0,0 +1,212 @@
+//! Flows started by [`Event`]s. See [`FlowAppExt::add_flow_on_event`](crate::app::FlowAppExt::add_flow_on_event)
+
+use std::{collections::VecDeque, future::Future, sync::Arc};
+
+use bevy::prelude::*;
+
+use crate::{context::FlowContext, error::FlowOutcome, handle::FlowTaskHandle, plugin::FlowTaskManager};
+
+
+/// What happens when an event arrives while the flow started by an
+/// earlier one is still running
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum EventFlowPolicy {
+    /// Start a flow for every event, no matter how many are already running
+    #[default]
+    Concurrent,
+    /// Drop events that arrive while the flow is running
+    Ignore,
+    /// Keep events that arrive while the flow is running, and start a flow
+    /// for each of them in turn, once the one before it has finished
+    Queue,
+    /// Cancel the running flow, and start a new one for the latest event.
+    /// The cancelled flows [`on_cancel`](FlowContext::on_cancel) hooks still run
+    Restart,
+}
+
+/// Creates the system that starts a flow for each `E`, following `policy`
+pub(crate) fn start_on_event<E, Func, Fut>(
+    policy: EventFlowPolicy, 
+    flow: Func,
+) -> impl FnMut(EventReader<E>, FlowTaskManager) + Send + Sync + 'static
+where
+    E: Event + Clone,
+    Func: Fn(FlowContext, E) -> Fut + Send + Sync + 'static,
+    Fut: Future + Send,
+    Fut::Output: FlowOutcome,
+{
+    let flow = Arc::new(flow);
+    let mut running: Option<FlowTaskHandle<Fut::Output>> = None;
+    let mut queued = VecDeque::new();
+
+    move |mut events: EventReader<E>, mut flows: FlowTaskManager| {
+        let mut start = |evt: E| {
+            let flow = flow.clone();
+            flows.start_checked(move |ctx| flow(ctx, evt), (), default(), Fut::Output::flow_error)
+        };
+        let busy = running.as_ref().is_some_and(|handle| !handle.is_finished());
+
+        match policy {
+            EventFlowPolicy::Concurrent => {
+                for evt in events.read() {
+                    start(evt.clone());
+                }
+            },
+            EventFlowPolicy::Ignore => {
+                let evt = events.read().next().cloned();
+                events.clear();
+                if let Some(evt) = evt.filter(|_| !busy) {
+                    running = Some(start(evt));
+                }
+            },
+            EventFlowPolicy::Queue => {
+                queued.extend(events.read().cloned());
+                if busy { return }
+                if let Some(evt) = queued.pop_front() {
+                    running = Some(start(evt));
+                }
+            },
+            EventFlowPolicy::Restart => {
+                let Some(evt) = events.read().last().cloned() else { return };
+                if let Some(old) = running.take() {
+                    old.cancel();
+                }
+                running = Some(start(evt));
+            },
+        }
+    }
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use std::{thread::sleep, time::Duration};
+
+    use bevy::prelude::*;
+
+    use crate::{plugin::FlowTaskList, prelude::*};
+
+    #[derive(Event, Clone)]
+    struct Go(u32);
+
+    /// Lets every flow that's been started finish
+    #[derive(Resource, Default)]
+    struct Release(bool);
+
+    #[derive(Resource, Default)]
+    struct Log {
+        started: Vec<u32>,
+        finished: Vec<u32>,
+        cancelled: Vec<u32>,
+    }
+
+    /// An app that starts a flow for each [`Go`] following `policy`, which 
+    /// runs until [`Release`] is set
+    fn app_with(policy: EventFlowPolicy) -> App {
+        let mut app = App::new();
+        app.add_plugins((MinimalPlugins, FlowTasksPlugin::default()))
+            .init_resource::<Log>()
+            .init_resource::<Release>()
+            .add_flow_on_event(policy, |ctx, Go(n)| async move {
+                ctx.on_cancel(move |world| world.resource_mut::<Log>().cancelled.push(n));
+                ctx.with_world(move |world| world.resource_mut::<Log>().started.push(n)).await;
+                ctx.await_resource::<Release>(|release| release.0).await;
+                ctx.with_world(move |world| world.resource_mut::<Log>().finished.push(n)).await;
+            });
+        app
+    }
+
+    /// Flows run on their own threads, so this gives them a moment between updates
+    fn update_until(app: &mut App, done: impl Fn(&World) -> bool) {
+        for _ in 0..500 {
+            app.update();
+            if done(app.world()) { return }
+            sleep(Duration::from_millis(1));
+        }
+        panic!("timed out");
+    }
+
+    fn update_for(app: &mut App, frames: u32) {
+        for _ in 0..frames {
+            app.update();
+            sleep(Duration::from_millis(1));
+        }
+    }
+
+    fn log(world: &World) -> &Log {
+        world.resource::<Log>()
+    }
+
+    fn running(world: &World) -> usize {
+        world.resource::<FlowTaskList>().len()
+    }
+
+    fn send(app: &mut App, events: impl IntoIterator<Item = u32>) {
+        app.world_mut().send_event_batch(events.into_iter().map(Go));
+    }
+
+    fn release(app: &mut App) {
+        app.world_mut().resource_mut::<Release>().0 = true;
+    }
+
+    #[test]
+    fn concurrent_starts_a_flow_for_every_event() {
+        let mut app = app_with(EventFlowPolicy::Concurrent);
+        send(&mut app, [1, 2, 3]);
+        update_until(&mut app, |world| log(world).started.len() == 3);
+        assert_eq!(running(app.world()), 3);
+
+        release(&mut app);
+        update_until(&mut app, |world| running(world) == 0);
+        assert_eq!(log(app.world()).finished.len(), 3);
+    }
+
+    #[test]
+    fn ignore_drops_events_while_running() {
+        let mut app = app_with(EventFlowPolicy::Ignore);
+        send(&mut app, [1, 2]);
+        update_until(&mut app, |world| !log(world).started.is_empty());
+        send(&mut app, [3]);
+        update_for(&mut app, 10);
+        assert_eq!(log(app.world()).started, [1]);
+        assert_eq!(running(app.world()), 1);
+
+        release(&mut app);
+        update_until(&mut app, |world| running(world) == 0);
+        send(&mut app, [4]);
+        update_until(&mut app, |world| log(world).finished.len() == 2);
+        assert_eq!(log(app.world()).started, [1, 4]);
+    }
+
+    #[test]
+    fn queue_runs_one_flow_at_a_time_in_order() {
+        let mut app = app_with(EventFlowPolicy::Queue);
+        send(&mut app, [1, 2, 3]);
+        update_until(&mut app, |world| !log(world).started.is_empty());
+        update_for(&mut app, 10);
+        assert_eq!(log(app.world()).started, [1]);
+        assert_eq!(running(app.world()), 1);
+
+        release(&mut app);
+        update_until(&mut app, |world| log(world).finished.len() == 3);
+        assert_eq!(log(app.world()).started, [1, 2, 3]);
+        assert_eq!(log(app.world()).finished, [1, 2, 3]);
+    }
+
+    #[test]
+    fn restart_cancels_the_running_flow() {
+        let mut app = app_with(EventFlowPolicy::Restart);
+        send(&mut app, [1]);
+        update_until(&mut app, |world| !log(world).started.is_empty());
+        send(&mut app, [2, 3]);
+        update_until(&mut app, |world| log(world).started.len() == 2 && running(world) == 1);
+        // only the latest event of a frame is kept
+        assert_eq!(log(app.world()).started, [1, 3]);
+        assert_eq!(log(app.world()).cancelled, [1]);
+
+        release(&mut app);
+        update_until(&mut app, |world| running(world) == 0);
+        assert_eq!(log(app.world()).finished, [3]);
+    }
+}
@@ -0,0 +1,143 @@
+//! The [`Flow`] trait, for anything that can be run as a flow
+
+use std::{any::type_name, borrow::Cow, future::Future};
+
+use crate::context::FlowContext;
+
+
+/// Something that can be run as a flow.
+///
+/// This is implemented for every `FnOnce(FlowContext) -> impl Future`, which
+/// covers plain `async fn`s, closures returning an `async move` block, and
+/// async closures, so none of them need a nightly compiler.
+///
+/// ```rust
+/// # use bevy::prelude::*;
+/// # use bevy_flow::prelude::*;
+/// #[derive(Resource, Clone)]
+/// struct Level(u32);
+///
+/// async fn load_level(ctx: FlowContext) {
+///     let _level = ctx.copy_resource::<Level>().await;
+///     // ...
+/// }
+///
+/// fn start_loading(mut flows: FlowTaskManager) {
+///     flows.start(load_level);
+///
+///     let level = Level(3);
+///     flows.start(move |ctx| async move {
+///         ctx.insert_resource(level).await;
+///     });
+/// }
+/// ```
+///
+/// Flows that are reused, or that should show up in tooling, can be types of
+/// their own. They're started with [`FlowTaskManager::start_flow`], or by name
+/// once they've been [registered](crate::app::FlowAppExt::register_flow).
+///
+/// ```rust
+/// # use bevy::prelude::*;
+/// # use bevy_flow::prelude::*;
+/// # #[derive(Resource)] struct Spawned(u32);
+/// #[derive(Default)]
+/// struct SpawnWave {
+///     delay_secs: f32,
+/// }
+///
+/// impl Flow for SpawnWave {
+///     type Input = u32;
+///     type Output = ();
+///
+///     fn name(&self) -> std::borrow::Cow<'static, str> {
+///         "spawn_wave".into()
+///     }
+///
+///     async fn run(self, ctx: FlowContext, enemies: u32) {
+///         ctx.sleep(std::time::Duration::from_secs_f32(self.delay_secs)).await;
+///         ctx.insert_resource(Spawned(enemies)).await;
+///     }
+/// }
+///
+/// fn start_waves(mut flows: FlowTaskManager) {
+///     flows.start_flow_with(SpawnWave { delay_secs: 2.0 }, 10);
+/// }
+/// ```
+///
+/// [`FlowTaskManager::start_flow`]: crate::plugin::FlowTaskManager::start_flow
+pub trait Flow: Send + 'static {
+    /// What the flow is started with, on top of the flow itself.
+    /// Flows that don't need anything else use `()`
+    type Input: Send + 'static;
+
+    /// What the flow returns once it finishes
+    type Output: Send + 'static;
+
+    /// The name of the flow, used in logs, [`FlowPanicked`](crate::plugin::FlowPanicked)
+    /// events, and to find [registered](crate::app::FlowAppExt::register_flow) flows.
+    /// Defaults to the type name.
+    fn name(&self) -> Cow<'static, str> {
+        type_name::<Self>().into()
+    }
+
+    /// Runs the flow, using `ctx` to reach the bevy app
+    fn run(self, ctx: FlowContext, input: Self::Input) -> impl Future<Output = Self::Output> + Send;
+}
+
+impl<Func, Fut> Flow for Func
+where
+    Func: FnOnce(FlowContext) -> Fut + Send + 'static,
+    Fut: Future + Send,
+    Fut::Output: Send + 'static,
+{
+    type Input = ();
+    type Output = Fut::Output;
+
+    fn run(self, ctx: FlowContext, _input: ()) -> impl Future<Output = Self::Output> + Send {
+        self(ctx)
+    }
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use std::{thread::sleep, time::Duration};
+
+    use bevy::prelude::*;
+
+    use crate::prelude::*;
+
+    struct Double;
+
+    impl Flow for Double {
+        type Input = u32;
+        type Output = u32;
+
+        async fn run(self, _ctx: FlowContext, input: u32) -> u32 {
+            input * 2
+        }
+    }
+
+    #[derive(Resource)]
+    struct Doubled(FlowTaskHandle<u32>);
+
+    #[test]
+    fn flow_output_can_be_read_from_its_handle() {
+        let mut app = App::new();
+        app.add_plugins((MinimalPlugins, FlowTasksPlugin::default()))
+            .add_systems(Startup, |mut commands: Commands, mut flows: FlowTaskManager| {
+                commands.insert_resource(Doubled(flows.start_flow_with_handle(Double, 21)));
+            });
+
+        let mut result = None;
+        // the flow runs on its own thread, so it takes a moment to finish
+        for _ in 0..200 {
+            app.update();
+            result = app.world().resource::<Doubled>().0.try_take_result();
+            if result.is_some() { break }
+            sleep(Duration::from_millis(1));
+        }
+        assert_eq!(result.map(Result::ok), Some(Some(42)));
+    }
+}
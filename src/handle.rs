@@ -0,0 +1,127 @@
+//! Handles for getting the values flows return
+
+use std::{future::{Future, IntoFuture}, pin::Pin, sync::Arc};
+
+use async_channel::{bounded, Receiver, Sender, TryRecvError};
+
+use crate::{error::FlowError, runner::{FlowControl, FlowTaskId}};
+
+
+/// A handle to a running flow, which will hold the value the flow returns 
+/// once it finishes.
+/// 
+/// Systems can check for the result with [`try_take_result`](Self::try_take_result),
+/// while other flows can simply `.await` the handle. Dropping the handle
+/// doesn't stop the flow, but [`cancel`](Self::cancel) does.
+/// 
+/// ```rust
+/// # use bevy::{prelude::*, app::AppExit};
+/// # use bevy_flow::prelude::*;
+/// #[derive(Resource)]
+/// struct Answer(FlowTaskHandle<u32>);
+/// 
+/// #[derive(Resource, Clone)]
+/// struct Base(u32);
+/// 
+/// fn main() {
+///     App::new()
+///         .add_plugins(MinimalPlugins)
+///         .add_plugins(FlowTasksPlugin::default())
+///         .insert_resource(Base(40))
+///         .add_systems(Startup, |mut flow: FlowTaskManager, mut cmds: Commands| {
+///             let handle = flow.start_with_handle(|ctx: FlowContext| async move {
+///                 // the first flow hands its result to the second one
+///                 ctx.copy_resource::<Base>().await.0 + 2
+///             });
+///             let handle = flow.start_with_handle(|_ctx: FlowContext| async move {
+///                 handle.await.unwrap()
+///             });
+///             cmds.insert_resource(Answer(handle));
+///         })
+///         .add_systems(Update, |answer: Res<Answer>, mut exits: EventWriter<AppExit>| {
+///             if let Some(value) = answer.0.try_take_result() {
+///                 assert_eq!(value, Ok(42));
+///                 exits.send(AppExit::Success);
+///             }
+///         })
+///         .run();
+/// }
+/// ```
+pub struct FlowTaskHandle<T> {
+    id: FlowTaskId,
+    recv: Receiver<Result<T, FlowError>>,
+    control: Arc<FlowControl>,
+}
+
+impl<T> Clone for FlowTaskHandle<T> {
+    fn clone(&self) -> Self {
+        Self {
+            id: self.id,
+            recv: self.recv.clone(),
+            control: self.control.clone(),
+        }
+    }
+}
+
+impl<T> FlowTaskHandle<T> {
+    /// Create a handle, and the [`Sender`] the flow will use to deliver its result
+    pub(crate) fn new(id: FlowTaskId, control: Arc<FlowControl>) -> (Self, Sender<Result<T, FlowError>>) {
+        let (send, recv) = bounded(1);
+        (Self { id, recv, control }, send)
+    }
+
+    /// The id of the flow this is a handle for
+    pub fn id(&self) -> FlowTaskId {
+        self.id
+    }
+
+    /// Returns `true` once the flow has stopped running, whether or
+    /// not it produced a value.
+    pub fn is_finished(&self) -> bool {
+        self.recv.is_closed()
+    }
+
+    /// Take the value the flow returned, if it has finished. Returns [`None`]
+    /// while the flow is still running.
+    /// 
+    /// The value can only be taken once, so if this handle has been cloned, 
+    /// only the first clone to ask will receive it. The rest get [`FlowError::NoResult`].
+    pub fn try_take_result(&self) -> Option<Result<T, FlowError>> {
+        match self.recv.try_recv() {
+            Ok(result) => Some(result),
+            Err(TryRecvError::Empty) => None,
+            Err(TryRecvError::Closed) => Some(Err(FlowError::NoResult)),
+        }
+    }
+
+    /// Ask the flow to stop. 
+    /// 
+    /// This is cooperative: the flows next request for the [`World`](bevy::prelude::World)
+    /// fails with [`FlowError::Cancelled`], and it can check 
+    /// [`FlowContext::is_cancelled`](crate::context::FlowContext::is_cancelled) 
+    /// while doing long running work. Before the flow is removed, its 
+    /// [`on_cancel`](crate::context::FlowContext::on_cancel) hooks are run.
+    pub fn cancel(&self) {
+        self.control.cancel();
+    }
+
+    /// Returns `true` if the flow has been asked to stop
+    pub fn is_cancelled(&self) -> bool {
+        self.control.is_cancelled()
+    }
+
+    pub(crate) fn control(&self) -> &Arc<FlowControl> {
+        &self.control
+    }
+}
+
+impl<T: Send + 'static> IntoFuture for FlowTaskHandle<T> {
+    type Output = Result<T, FlowError>;
+    type IntoFuture = Pin<Box<dyn Future<Output = Result<T, FlowError>> + Send + Sync>>;
+
+    fn into_future(self) -> Self::IntoFuture {
+        Box::pin(async move {
+            self.recv.recv().await.unwrap_or(Err(FlowError::NoResult))
+        })
+    }
+}
@@ -0,0 +1,67 @@
+//! Finding running flows by name, or by a key they were started with
+
+use std::{any::{Any, TypeId}, borrow::Cow, hash::Hash, sync::Arc};
+
+use bevy::utils::HashMap;
+
+use crate::runner::{FlowControl, FlowTaskId};
+
+
+/// A flow that can be found by its name or key
+pub(crate) struct KeyedFlow {
+    pub(crate) id: FlowTaskId,
+    pub(crate) control: Arc<FlowControl>,
+}
+
+impl KeyedFlow {
+    /// Returns `false` once the flow has been cancelled, or has finished
+    pub(crate) fn is_running(&self) -> bool {
+        !self.control.is_cancelled() && !self.control.is_finished()
+    }
+}
+
+/// The names and keys of running flows. Each map only holds one flow per 
+/// name or key, and forgets flows that have stopped running as new ones are added
+#[derive(Default)]
+pub(crate) struct FlowKeys {
+    named: HashMap<Cow<'static, str>, KeyedFlow>,
+    /// A `HashMap<K, KeyedFlow>` for each type of key
+    unique: HashMap<TypeId, Box<dyn Any + Send + Sync>>,
+}
+
+impl FlowKeys {
+    /// The latest running flow started as `name`
+    pub(crate) fn named(&self, name: &str) -> Option<&KeyedFlow> {
+        self.named.get(name).filter(|flow| flow.is_running())
+    }
+
+    pub(crate) fn insert_named(&mut self, name: Cow<'static, str>, flow: KeyedFlow) {
+        self.named.retain(|_, flow| flow.is_running());
+        self.named.insert(name, flow);
+    }
+
+    /// The running flow started with `key`
+    pub(crate) fn unique<K>(&self, key: &K) -> Option<&KeyedFlow>
+    where
+        K: Hash + Eq + Send + Sync + 'static,
+    {
+        self.unique.get(&TypeId::of::<K>())?
+            .downcast_ref::<HashMap<K, KeyedFlow>>()?
+            .get(key)
+            .filter(|flow| flow.is_running())
+    }
+
+    pub(crate) fn insert_unique<K>(&mut self, key: K, flow: KeyedFlow)
+    where
+        K: Hash + Eq + Send + Sync + 'static,
+    {
+        let Some(flows) = self.unique
+            .entry(TypeId::of::<K>())
+            .or_insert_with(|| Box::new(HashMap::<K, KeyedFlow>::default()))
+            .downcast_mut::<HashMap<K, KeyedFlow>>()
+        else { unreachable!("flow keys are stored by their TypeId") };
+
+        flows.retain(|_, flow| flow.is_running());
+        flows.insert(key, flow);
+    }
+}
@@ -4,16 +4,39 @@
 #![warn(clippy::missing_panics_doc)]
 #![warn(clippy::absolute_paths)]
 
-#![feature(async_closure)]
-#![feature(unboxed_closures)]
-#![feature(exact_size_is_empty)]
-
+pub mod app;
+pub mod blocking;
+pub mod commands;
 pub mod context;
+mod cursor;
+pub mod entity;
+pub mod error;
+pub mod event;
+pub mod flow;
+pub mod handle;
+mod keys;
 pub mod plugin;
+pub mod registry;
 pub mod runner;
+pub mod scheduler;
+pub mod select;
+pub mod start;
+pub mod waiter;
 
 /// The stuff you will likely need, all in one place
 pub mod prelude {
+    pub use crate::app::FlowAppExt;
+    pub use crate::commands::FlowCommands;
     pub use crate::context::{FlowContext, WorldRef};
-    pub use crate::plugin::{FlowTasksPlugin, FlowTaskSystemSet, FlowTaskManager};
+    pub use crate::entity::{EntityFlowExt, FlowOwner};
+    pub use crate::error::FlowError;
+    pub use crate::event::EventFlowPolicy;
+    pub use crate::flow::Flow;
+    pub use crate::handle::FlowTaskHandle;
+    pub use crate::plugin::{FlowFailed, FlowPanicked, FlowTasksPlugin, FlowTaskSystemSet, FlowTaskManager};
+    pub use crate::runner::FlowExecutor;
+    pub use crate::scheduler::FlowPriority;
+    pub use crate::select::FlowFutureExt;
+    pub use crate::start::StartFlowExt;
+    pub use crate::waiter::FlowClock;
 }
@@ -1,10 +1,35 @@
-//!
+//! The bevy side of flows: the plugin, and the tools for starting and tracking flows
 
-use std::{future::Future, sync::atomic::{AtomicU64, Ordering}};
+use std::{
+    any::Any,
+    borrow::Cow,
+    future::Future, 
+    hash::Hash,
+    sync::Arc, 
+    time::Duration,
+};
 
-use bevy::{ecs::system::{SystemParam, SystemState}, prelude::*, utils::hashbrown::HashMap};
+use bevy::{
+    ecs::{schedule::{InternedScheduleLabel, ScheduleLabel}, system::SystemParam}, 
+    prelude::*, 
+    state::app::StatesPlugin, 
+    utils::{hashbrown::HashMap, HashSet, Instant}
+};
 
-use crate::{context::FlowContext, runner::{FlowTaskId, FlowTaskRunner}};
+use async_channel::Sender;
+
+use crate::{
+    commands::{FlowCommand, FlowCommandQueue},
+    context::FlowContext, 
+    error::{FlowError, FlowOutcome}, 
+    flow::Flow,
+    handle::FlowTaskHandle, 
+    keys::{FlowKeys, KeyedFlow},
+    registry::FlowRegistry,
+    runner::{FlowControl, FlowExecutor, FlowLinks, FlowScope, FlowSpawner, FlowTaskId, FlowTaskRunner, LocalFlows},
+    scheduler::{FlowPriority, LoanQueue},
+    waiter::{check_waiters, FlowNow, FlowWaiters, WaitCheck},
+};
 
 
 /// The [`SystemSet`] for when [`FlowTasksPlugin`] executes the 
@@ -19,24 +44,215 @@ pub struct FlowTaskSystemSet;
 /// and action scheduling to be done without the complexities of multiple 
 /// systems coordinated by [`State`]s and [`Event`]s.
 /// 
-/// Execution always takes place in the [`Update`] Schedule.
+/// By default, execution takes place in the [`Update`] Schedule. This can be 
+/// changed with [`FlowTasksPlugin::in_schedules`].
+/// 
+/// For timing control, see [`FlowTaskSystemSet`]. To choose how flows are
+/// executed, see [`FlowTasksPlugin::with_executor`].
+/// 
+/// ```rust
+/// # use std::time::Duration;
+/// # use bevy::prelude::*;
+/// # use bevy_flow::prelude::*;
+/// use bevy::ecs::schedule::ScheduleLabel;
 /// 
-/// For timing control, see [`FlowTaskSystemSet`].
-pub struct FlowTasksPlugin;
+/// App::new()
+///     .add_plugins(MinimalPlugins)
+///     .add_plugins(FlowTasksPlugin::default()
+///         .with_executor(FlowExecutor::AsyncComputePool)
+///         .in_schedules([FixedUpdate.intern(), PostUpdate.intern()])
+///         .with_loan_budget(Duration::from_millis(2))
+///     );
+/// ```
+#[derive(Clone, Debug)]
+pub struct FlowTasksPlugin {
+    /// Where flows are polled. See [`FlowExecutor`]
+    pub executor: FlowExecutor,
+    /// The schedules flows are lent the [`World`] in. [`Update`] if this is 
+    /// empty, which it is by default
+    pub schedules: Vec<InternedScheduleLabel>,
+    /// How many messages can be waiting between a flow and the app, 
+    /// like requests for the [`World`]. Defaults to `5`
+    pub channel_capacity: usize,
+    /// What the names of threads spawned for flows start with. Defaults to `"flow"`.
+    /// 
+    /// Each [`FlowExecutor::Thread`] is named `"{prefix}-{n}"`, counting up from 
+    /// `1`. Bevy numbers the threads of a [`FlowExecutor::DedicatedPool`] itself.
+    pub thread_prefix: String,
+    /// The stack size of threads spawned for flows. Uses the platforms default if [`None`]
+    pub stack_size: Option<usize>,
+    /// The most time spent lending the [`World`] to flows each frame, across 
+    /// all schedules. Flows that miss out go first next frame. Unlimited if [`None`]
+    pub loan_budget: Option<Duration>,
+    /// Each [`FlowPriority::Background`] flow is lent the [`World`] at most 
+    /// once every this many frames. Defaults to `10`
+    pub background_interval: u32,
+    /// How many times in a row a flow can be lent the [`World`] each time the
+    /// schedule runs, as long as it keeps asking for it. Defaults to `1`.
+    /// See [`FlowTasksPlugin::with_eager_loans`]
+    pub loans_per_run: u32,
+    /// How long to wait for a flow to ask for the [`World`] again, when 
+    /// [`loans_per_run`](Self::loans_per_run) is more than `1`. Defaults to 100µs
+    pub eager_linger: Duration,
+}
+
+impl Default for FlowTasksPlugin {
+    fn default() -> Self {
+        Self {
+            executor: default(),
+            schedules: Vec::new(),
+            channel_capacity: 5,
+            thread_prefix: "flow".to_string(),
+            stack_size: None,
+            loan_budget: None,
+            background_interval: 10,
+            loans_per_run: 1,
+            eager_linger: Duration::from_micros(100),
+        }
+    }
+}
+
+impl FlowTasksPlugin {
+    /// Choose where the futures of flows are polled. Defaults to 
+    /// [`FlowExecutor::Thread`], which gives each flow its own thread.
+    pub fn with_executor(mut self, executor: FlowExecutor) -> Self {
+        self.executor = executor;
+        self
+    }
+
+    /// Lend the [`World`] to flows in `schedule`, instead of [`Update`]
+    pub fn in_schedule(self, schedule: impl ScheduleLabel) -> Self {
+        self.in_schedules([schedule.intern()])
+    }
+
+    /// Lend the [`World`] to flows in each of `schedules`, instead of [`Update`]. 
+    /// Use [`ScheduleLabel::intern`] to mix different kinds of schedule.
+    pub fn in_schedules(mut self, schedules: impl IntoIterator<Item = impl ScheduleLabel>) -> Self {
+        self.schedules = schedules.into_iter().map(|label| label.intern()).collect();
+        self
+    }
+
+    /// The schedules flows that aren't pinned are lent the [`World`] in, each only once
+    fn default_schedules(&self) -> Vec<InternedScheduleLabel> {
+        if self.schedules.is_empty() { return vec![Update.intern()] }
+
+        let mut seen = HashSet::new();
+        self.schedules.iter().copied().filter(|schedule| seen.insert(*schedule)).collect()
+    }
+
+    /// Set how many messages can be waiting between a flow and the app. 
+    /// This is at least `1`
+    pub fn with_channel_capacity(mut self, capacity: usize) -> Self {
+        self.channel_capacity = capacity;
+        self
+    }
+
+    /// Set what the names of threads spawned for flows start with, and optionally
+    /// their stack size. See [`thread_prefix`](Self::thread_prefix).
+    /// This applies to [`FlowExecutor::Thread`] and [`FlowExecutor::DedicatedPool`]
+    pub fn with_threads(mut self, prefix: impl Into<String>, stack_size: Option<usize>) -> Self {
+        self.thread_prefix = prefix.into();
+        self.stack_size = stack_size;
+        self
+    }
+
+    /// Limit how long is spent lending the [`World`] to flows each frame. 
+    /// 
+    /// Flows are lent the `World` round-robin, and flows that miss out because 
+    /// the budget is spent go first next frame. A loan that's started isn't 
+    /// interupted, so a flow that holds on to the `World` can still go over.
+    pub fn with_loan_budget(mut self, budget: Duration) -> Self {
+        self.loan_budget = Some(budget);
+        self
+    }
+
+    /// Keep lending the [`World`] to a flow for as long as it keeps asking, up 
+    /// to `loans_per_run` times each time the schedule runs, and within the 
+    /// [loan budget](Self::with_loan_budget). So a flow that calls 
+    /// [`set_state`](FlowContext::set_state), then [`send_event`](FlowContext::send_event), 
+    /// then [`insert_resource`](FlowContext::insert_resource) can do all three in
+    /// one frame, instead of three.
+    /// 
+    /// After each loan, the app waits up to `linger` for the flow to ask again.
+    /// That wait holds up the app, so keep it short. 
+    /// 
+    /// To group operations into one loan explicitly, see [`FlowContext::batch`]
+    pub fn with_eager_loans(mut self, loans_per_run: u32, linger: Duration) -> Self {
+        self.loans_per_run = loans_per_run;
+        self.eager_linger = linger;
+        self
+    }
+
+    /// Lend the [`World`] to each [`FlowPriority::Background`] flow at most 
+    /// once every `frames` frames
+    pub fn with_background_interval(mut self, frames: u32) -> Self {
+        self.background_interval = frames;
+        self
+    }
+}
 
 impl Plugin for FlowTasksPlugin {
-        fn build(&self, app: &mut App) {
+    fn build(&self, app: &mut App) {
+        if !app.is_plugin_added::<StatesPlugin>() {
+            app.add_plugins(StatesPlugin);
+        }
+
+        let (waiters, waiter_send) = FlowWaiters::new();
+        let spawner = FlowSpawner::new(
+            &self.executor, 
+            self.channel_capacity, 
+            &self.thread_prefix, 
+            self.stack_size,
+        );
+
         app
             .init_state::<IsFlowing>()
-            .init_resource::<FlowTaskList>()
+            .insert_resource(FlowTaskList::new(spawner, waiter_send, waiters.now(), self))
+            .insert_resource(waiters)
+            .init_non_send_resource::<LocalFlows>()
+            .add_event::<FlowFailed>()
+            .add_event::<FlowPanicked>()
 
-            .add_systems(Update, 
-                run_tasks.in_set(FlowTaskSystemSet)
-            )
+            // a schedule can't be changed while it's running, so between them 
+            // these can add loan systems to any schedule flows are pinned to
+            .add_systems(First, (start_loan_frame, install_loan_systems(First.intern())))
+            .add_systems(Last, install_loan_systems(Last.intern()))
         ;
+
+        for schedule in self.default_schedules() {
+            app.add_systems(schedule, 
+                (check_waiters, run_tasks_in(schedule)).chain().in_set(FlowTaskSystemSet)
+            );
+        }
     }
 }
 
+/// Sent when a flow started with [`FlowTaskManager::start`] returns an error.
+/// See [`FlowOutcome`]
+#[derive(Clone, Debug, Event)]
+pub struct FlowFailed {
+    /// The flow that failed
+    pub id: FlowTaskId,
+    /// The name of the flow. See [`FlowTaskRunner::name`]
+    pub name: String,
+    /// What the flow failed with
+    pub error: FlowError,
+}
+
+/// Sent when a flow panics. 
+/// 
+/// The panic is caught, so it doesn't take down the rest of the app, and the
+/// flow is removed from [`FlowTaskList`].
+#[derive(Clone, Debug, Event)]
+pub struct FlowPanicked {
+    /// The flow that panicked
+    pub id: FlowTaskId,
+    /// The name of the flow. See [`FlowTaskRunner::name`]
+    pub name: String,
+    /// The panic message
+    pub message: String,
+}
+
 #[derive(Clone, Debug, Default, Hash, PartialEq, Eq, States)]
 enum IsFlowing {
     #[default]
@@ -45,21 +261,172 @@ enum IsFlowing {
 }
 
 /// All of the Flow Tasks that are in progress
-#[derive(Default, Resource, Deref, DerefMut)]
+#[derive(Resource, Deref, DerefMut)]
 pub struct FlowTaskList {
     #[deref]
     tasks: HashMap<FlowTaskId, FlowTaskRunner>,
-    next_id: AtomicU64,
+    spawner: FlowSpawner,
+    waiters: Sender<WaitCheck>,
+    now: Arc<FlowNow>,
+    commands: FlowCommandQueue,
+    command_send: Sender<FlowCommand>,
+    queue: LoanQueue,
+    /// Where flows that aren't pinned to a schedule are lent the `World`
+    default_schedules: Vec<InternedScheduleLabel>,
+    /// Schedules with a system lending the `World` to flows
+    installed: HashSet<InternedScheduleLabel>,
+    /// Flows started with a name or key, so they can be found again
+    keys: FlowKeys,
 }
 
 impl FlowTaskList {
-    fn clean(&mut self) {
-        self.tasks.retain(|_id, flow| !flow.is_finished())
+    fn new(spawner: FlowSpawner, waiters: Sender<WaitCheck>, now: Arc<FlowNow>, plugin: &FlowTasksPlugin) -> Self {
+        let (commands, command_send) = FlowCommandQueue::new();
+        Self {
+            tasks: default(),
+            spawner,
+            waiters,
+            now,
+            commands,
+            command_send,
+            queue: LoanQueue::new(plugin),
+            default_schedules: plugin.default_schedules(),
+            installed: plugin.default_schedules().into_iter().collect(),
+            keys: default(),
+        }
     }
 
-    fn next_id(&mut self) -> u64 {
-        self.next_id.fetch_add(1, Ordering::Acquire)
+    /// Remove finished flows, returning the ones that failed and panicked
+    fn clean(&mut self) -> (Vec<FlowFailed>, Vec<FlowPanicked>) {
+        let mut failed = Vec::new();
+        let mut panicked = Vec::new();
+        self.tasks.retain(|id, flow| {
+            if !flow.is_finished() { return true }
+            flow.wrap_up();
+
+            match flow.take_failure() {
+                Some(FlowError::Panicked(message)) => {
+                    error!("Flow {} ({id:?}) panicked: {message}", flow.name());
+                    panicked.push(FlowPanicked { id: *id, name: flow.name().to_string(), message });
+                },
+                Some(error) => failed.push(FlowFailed { id: *id, name: flow.name().to_string(), error }),
+                None => { },
+            }
+            false
+        });
+        (failed, panicked)
+    }
+
+    /// Add the flow `runner` creates to the list, as `id`
+    fn start_runner<T>(
+        &mut self, 
+        id: FlowTaskId,
+        assets: Option<AssetServer>,
+        options: FlowOptions,
+        runner: impl FnOnce(FlowLinks, &FlowSpawner, Sender<Result<T, FlowError>>, Arc<FlowControl>) -> FlowTaskRunner,
+    ) -> FlowTaskHandle<T> {
+        let control = Arc::new(FlowControl::pinned_to(options.schedule)
+            .owned_by(options.owner)
+            .scoped_to(options.scope));
+        let (handle, result) = FlowTaskHandle::new(id, control.clone());
+
+        self.add_runner(id, assets, |links, spawner| {
+            let mut runner = runner(links, spawner, result, control);
+            runner.set_priority(options.priority);
+            if let Some(name) = options.name {
+                runner.set_name(name);
+            }
+            runner
+        });
+        handle
     }
+
+    /// Add the flow `runner` creates to the list as `id`, for flows that 
+    /// already have a handle
+    fn add_runner(
+        &mut self,
+        id: FlowTaskId,
+        assets: Option<AssetServer>,
+        runner: impl FnOnce(FlowLinks, &FlowSpawner) -> FlowTaskRunner,
+    ) {
+        let links = FlowLinks {
+            assets,
+            waiters: self.waiters.clone(),
+            commands: self.command_send.clone(),
+            now: self.now.clone(),
+        };
+        let runner = runner(links, &self.spawner);
+        debug!("Starting flow {} ({id:?})", runner.name());
+
+        let old = self.tasks.insert(id, runner);
+        debug_assert!(old.is_none());
+    }
+}
+
+/// Start a flow made by [`FlowContext::spawn_child`], which has already 
+/// set up its handle
+pub(crate) fn start_child<F: Flow>(
+    world: &mut World,
+    id: FlowTaskId,
+    flow: F,
+    input: F::Input,
+    result: Sender<Result<F::Output, FlowError>>,
+    control: Arc<FlowControl>,
+) {
+    let assets = world.get_resource::<AssetServer>().cloned();
+    // the parent is a running flow, so the plugin is there
+    let Some(mut list) = world.get_resource_mut::<FlowTaskList>() else { return };
+    list.add_runner(id, assets, |links, spawner| {
+        FlowTaskRunner::new_with(flow, input, links, spawner, result, control, |_| None)
+    });
+    world.resource_mut::<NextState<IsFlowing>>().set(IsFlowing::Yes);
+}
+
+/// Start a flow as `id`, from anywhere with access to the [`World`]. 
+/// See [`StartFlowExt`](crate::start::StartFlowExt)
+pub(crate) fn start_in_world<F>(
+    world: &mut World, 
+    id: FlowTaskId, 
+    flow: F, 
+    input: F::Input, 
+    options: FlowOptions,
+) -> FlowTaskHandle<F::Output>
+where
+    F: Flow,
+    F::Output: FlowOutcome,
+{
+    let assets = world.get_resource::<AssetServer>().cloned();
+    let Some(mut list) = world.get_resource_mut::<FlowTaskList>() else {
+        panic!("can't start flow {}, the FlowTasksPlugin hasn't been added", flow.name());
+    };
+    let handle = list.start_runner(id, assets, options, |links, spawner, result, control| {
+        FlowTaskRunner::new_with(flow, input, links, spawner, result, control, F::Output::flow_error)
+    });
+    world.resource_mut::<NextState<IsFlowing>>().set(IsFlowing::Yes);
+    handle
+}
+
+
+/// How a flow is set up when it's started
+#[derive(Default)]
+pub(crate) struct FlowOptions {
+    pub(crate) schedule: Option<InternedScheduleLabel>,
+    pub(crate) priority: FlowPriority,
+    pub(crate) owner: Option<Entity>,
+    pub(crate) scope: Option<FlowScope>,
+    /// Replaces [`Flow::name`]
+    pub(crate) name: Option<Cow<'static, str>>,
+}
+
+/// A [`FlowScope`] that lasts until the app leaves `state`. Flows started 
+/// before the app gets to `state` wait for it to be entered and left again.
+pub(crate) fn state_scope<S: States>(state: S) -> FlowScope {
+    let mut entered = false;
+    Box::new(move |world: &World| {
+        let current = world.get_resource::<State<S>>().is_some_and(|now| *now.get() == state);
+        entered |= current;
+        current || !entered
+    })
 }
 
 
@@ -71,13 +438,13 @@ pub struct FlowTaskManager<'w, 's> {
     next: ResMut<'w, NextState<IsFlowing>>,
     list: ResMut<'w, FlowTaskList>,
     assets: Option<Res<'w, AssetServer>>,
+    registry: Option<Res<'w, FlowRegistry>>,
 }
 
 impl<'w, 's> FlowTaskManager<'w, 's> {
     /// Create and start a flow task.
     /// 
     /// ```rust
-    /// # #![feature(async_closure)]
     /// # use bevy::{prelude::*, app::AppExit};
     /// # use bevy_flow::prelude::*;
     /// 
@@ -109,9 +476,9 @@ impl<'w, 's> FlowTaskManager<'w, 's> {
     /// fn main() {
     ///     let mut app = App::new();
     ///     app
-    ///         .init_state::<TerrainState>()
     ///         .add_plugins(MinimalPlugins)
-    ///         .add_plugins(FlowTasksPlugin)
+    ///         .add_plugins(FlowTasksPlugin::default())
+    ///         .init_state::<TerrainState>()
     ///         .add_systems(Startup, start_terrain_generation)
     ///         .add_systems(OnEnter(TerrainState::Ready), terrain_ready)
     ///         .run();
@@ -122,7 +489,7 @@ impl<'w, 's> FlowTaskManager<'w, 's> {
     /// }
     /// 
     /// 
-    /// async fn do_terrain_generation(mut ctx: FlowContext) {
+    /// async fn do_terrain_generation(ctx: FlowContext) {
     ///     // actions which don't use `ctx` will run independent
     ///     // of the bevy app, so you don't have to worry about blocking
     ///     let mut terrain = MyTerrainResource::new();
@@ -130,32 +497,330 @@ impl<'w, 's> FlowTaskManager<'w, 's> {
     /// 
     ///     // this will wait until the right time in the update cycle, and
     ///     // borrow access to `World` to accomplish the task.
-    ///     ctx.insert_resource(terrain);
+    ///     ctx.insert_resource(terrain).await;
     /// 
     ///     // this won't happen until the update after the previous line
-    ///     ctx.set_state(TerrainState::Ready);
+    ///     ctx.set_state(TerrainState::Ready).await;
     /// }
     /// 
     /// fn terrain_ready(mut exits: EventWriter<AppExit>) {
-    ///     exits.send(AppExit);
+    ///     exits.send(AppExit::Success);
     /// }
     /// ```
-    pub fn start<Func, Fut>(&mut self, task_fn: Func) -> FlowTaskId
+    /// 
+    /// Flows can also return `Result<(), E>`, in which case a [`FlowFailed`]
+    /// event is sent if they return an error.
+    pub fn start<Func, Fut, Out>(&mut self, task_fn: Func) -> FlowTaskId
     where
-        Func: FnOnce(FlowContext) -> Fut + Send + Sync + 'static,
-        Fut: Future<Output=()> + Send + Sync,
+        Func: FnOnce(FlowContext) -> Fut + Send + 'static,
+        Fut: Future<Output=Out> + Send,
+        Out: FlowOutcome,
     {
-        let assets = self.assets.as_ref().map(|a| (*a).clone());
-        let runner = FlowTaskRunner::new(task_fn, assets);
-        let id = self.next_flow_task_id();
+        self.start_checked(task_fn, (), default(), Out::flow_error).id()
+    }
+
+    /// Create and start a flow task with a [`FlowPriority`] other than 
+    /// [`Normal`](FlowPriority::Normal). Otherwise this is the same as [`start`](Self::start)
+    /// 
+    /// ```rust
+    /// # use bevy::prelude::*;
+    /// # use bevy_flow::prelude::*;
+    /// fn reprocess_assets(mut flows: FlowTaskManager) {
+    ///     flows.start_with_priority(FlowPriority::Background, |ctx: FlowContext| async move {
+    ///         // only borrows the `World` once every few frames
+    ///     });
+    /// }
+    /// ```
+    pub fn start_with_priority<Func, Fut, Out>(&mut self, priority: FlowPriority, task_fn: Func) -> FlowTaskId
+    where
+        Func: FnOnce(FlowContext) -> Fut + Send + 'static,
+        Fut: Future<Output=Out> + Send,
+        Out: FlowOutcome,
+    {
+        let options = FlowOptions { priority, ..default() };
+        self.start_checked(task_fn, (), options, Out::flow_error).id()
+    }
+
+    /// Create and start a flow task which is only lent the [`World`] while 
+    /// `schedule` runs, instead of the schedules the [`FlowTasksPlugin`] was
+    /// set up with. Otherwise this is the same as [`start`](Self::start)
+    /// 
+    /// ```rust
+    /// # use bevy::prelude::*;
+    /// # use bevy_flow::prelude::*;
+    /// fn start_cutscene(mut flows: FlowTaskManager) {
+    ///     flows.start_in(FixedPostUpdate, |ctx: FlowContext| async move {
+    ///         // reads transforms right after physics has moved them
+    ///     });
+    /// }
+    /// ```
+    /// 
+    /// A flow can move to another schedule with [`FlowContext::switch_schedule`]
+    pub fn start_in<Func, Fut, Out>(&mut self, schedule: impl ScheduleLabel, task_fn: Func) -> FlowTaskId
+    where
+        Func: FnOnce(FlowContext) -> Fut + Send + 'static,
+        Fut: Future<Output=Out> + Send,
+        Out: FlowOutcome,
+    {
+        let options = FlowOptions { schedule: Some(schedule.intern()), ..default() };
+        self.start_checked(task_fn, (), options, Out::flow_error).id()
+    }
+
+    /// Create and start a flow task which is cancelled when the app leaves `state`,
+    /// like bevy's [`StateScoped`] entities. Its [`on_cancel`](FlowContext::on_cancel) 
+    /// hooks run right after [`OnExit`]. Otherwise this is the same as [`start`](Self::start)
+    /// 
+    /// If the app isn't in `state` yet, the flow runs until `state` has been 
+    /// entered, and then left.
+    /// 
+    /// ```rust
+    /// # use bevy::prelude::*;
+    /// # use bevy_flow::prelude::*;
+    /// #[derive(Clone, Copy, Debug, Default, Hash, PartialEq, Eq, States)]
+    /// enum GameState {
+    ///     #[default]
+    ///     Loading,
+    ///     Playing,
+    /// }
+    /// 
+    /// fn start_loading(mut flows: FlowTaskManager) {
+    ///     flows.start_scoped(GameState::Loading, |ctx: FlowContext| async move {
+    ///         ctx.on_cancel(|world| { world.remove_resource::<LoadingScreen>(); });
+    ///         // ...
+    ///     });
+    /// }
+    /// # #[derive(Resource, Default)] struct LoadingScreen;
+    /// ```
+    /// 
+    /// To start a flow each time a state is entered, see 
+    /// [`FlowAppExt::add_flow_on_enter`](crate::app::FlowAppExt::add_flow_on_enter)
+    pub fn start_scoped<S, Func, Fut, Out>(&mut self, state: S, task_fn: Func) -> FlowTaskId
+    where
+        S: States,
+        Func: FnOnce(FlowContext) -> Fut + Send + 'static,
+        Fut: Future<Output=Out> + Send,
+        Out: FlowOutcome,
+    {
+        let options = FlowOptions { scope: Some(state_scope(state)), ..default() };
+        self.start_checked(task_fn, (), options, Out::flow_error).id()
+    }
+
+    /// Create and start a flow task with a name, which is used in logs and 
+    /// [`FlowPanicked`] events instead of the type name of `task_fn`. 
+    /// Otherwise this is the same as [`start`](Self::start)
+    /// 
+    /// The flow can be found again with [`find_named`](Self::find_named).
+    /// 
+    /// ```rust
+    /// # use bevy::prelude::*;
+    /// # use bevy_flow::prelude::*;
+    /// fn load_level(mut flows: FlowTaskManager) {
+    ///     flows.start_named("load_level_3", |ctx: FlowContext| async move {
+    ///         // ...
+    ///     });
+    /// }
+    /// 
+    /// fn skip_loading(mut flows: FlowTaskManager) {
+    ///     if let Some(id) = flows.find_named("load_level_3") {
+    ///         flows.stop(id);
+    ///     }
+    /// }
+    /// ```
+    pub fn start_named<Func, Fut, Out>(&mut self, name: impl Into<Cow<'static, str>>, task_fn: Func) -> FlowTaskId
+    where
+        Func: FnOnce(FlowContext) -> Fut + Send + 'static,
+        Fut: Future<Output=Out> + Send,
+        Out: FlowOutcome,
+    {
+        let name = name.into();
+        let options = FlowOptions { name: Some(name.clone()), ..default() };
+        let handle = self.start_checked(task_fn, (), options, Out::flow_error);
+        self.list.keys.insert_named(name, KeyedFlow { id: handle.id(), control: handle.control().clone() });
+        handle.id()
+    }
+
+    /// Create and start a flow task, unless one started with the same `key`
+    /// is still running. Returns the id of whichever flow is running as `key`.
+    /// 
+    /// Keys can be anything that's [`Hash`] and [`Eq`]. Keys of different 
+    /// types never match. To cancel the running flow instead, see 
+    /// [`replace_unique`](Self::replace_unique)
+    /// 
+    /// ```rust
+    /// # use bevy::prelude::*;
+    /// # use bevy_flow::prelude::*;
+    /// #[derive(Hash, PartialEq, Eq)]
+    /// struct Autosave;
+    /// 
+    /// fn autosave(mut flows: FlowTaskManager) {
+    ///     // does nothing while the last autosave is still going
+    ///     flows.start_unique(Autosave, |ctx: FlowContext| async move {
+    ///         // ...
+    ///     });
+    /// }
+    /// ```
+    pub fn start_unique<K, Func, Fut, Out>(&mut self, key: K, task_fn: Func) -> FlowTaskId
+    where
+        K: Hash + Eq + Send + Sync + 'static,
+        Func: FnOnce(FlowContext) -> Fut + Send + 'static,
+        Fut: Future<Output=Out> + Send,
+        Out: FlowOutcome,
+    {
+        if let Some(running) = self.list.keys.unique(&key) {
+            return running.id
+        }
+        let handle = self.start_checked(task_fn, (), default(), Out::flow_error);
+        self.list.keys.insert_unique(key, KeyedFlow { id: handle.id(), control: handle.control().clone() });
+        handle.id()
+    }
+
+    /// Create and start a flow task, cancelling any flow started with the 
+    /// same `key` that's still running. See [`start_unique`](Self::start_unique)
+    pub fn replace_unique<K, Func, Fut, Out>(&mut self, key: K, task_fn: Func) -> FlowTaskId
+    where
+        K: Hash + Eq + Send + Sync + 'static,
+        Func: FnOnce(FlowContext) -> Fut + Send + 'static,
+        Fut: Future<Output=Out> + Send,
+        Out: FlowOutcome,
+    {
+        if let Some(running) = self.list.keys.unique(&key) {
+            debug!("Cancelling flow {:?}, as it's being replaced", running.id);
+            running.control.cancel();
+        }
+        let handle = self.start_checked(task_fn, (), default(), Out::flow_error);
+        self.list.keys.insert_unique(key, KeyedFlow { id: handle.id(), control: handle.control().clone() });
+        handle.id()
+    }
+
+    /// The id of the latest flow started with [`start_named`](Self::start_named)
+    /// as `name`, if it's still running
+    pub fn find_named(&self, name: &str) -> Option<FlowTaskId> {
+        self.list.keys.named(name).map(|flow| flow.id)
+    }
+
+    /// The id of the flow started with [`start_unique`](Self::start_unique) 
+    /// or [`replace_unique`](Self::replace_unique) as `key`, if it's still running
+    pub fn find_unique<K>(&self, key: &K) -> Option<FlowTaskId>
+    where
+        K: Hash + Eq + Send + Sync + 'static,
+    {
+        self.list.keys.unique(key).map(|flow| flow.id)
+    }
 
-        let old = self.list.insert(id, runner);
-        assert!(old.is_none());
+    /// Create and start a flow task which returns a value. 
+    /// 
+    /// The returned [`FlowTaskHandle`] can be polled by systems with 
+    /// [`FlowTaskHandle::try_take_result`], or `.await`ed by other flows.
+    pub fn start_with_handle<Func, Fut, T>(&mut self, task_fn: Func) -> FlowTaskHandle<T>
+    where
+        Func: FnOnce(FlowContext) -> Fut + Send + 'static,
+        Fut: Future<Output=T> + Send,
+        T: Send + 'static,
+    {
+        self.start_checked(task_fn, (), default(), |_| None)
+    }
+
+    /// Start a [`Flow`] that doesn't need any [`Input`](Flow::Input). 
+    /// Otherwise this is the same as [`start`](Self::start)
+    pub fn start_flow<F>(&mut self, flow: F) -> FlowTaskId
+    where
+        F: Flow<Input = ()>,
+        F::Output: FlowOutcome,
+    {
+        self.start_flow_with(flow, ())
+    }
+
+    /// Start a [`Flow`] with its [`Input`](Flow::Input). 
+    /// Otherwise this is the same as [`start`](Self::start). 
+    /// Use [`start_flow_with_handle`](Self::start_flow_with_handle) to get what the flow returns.
+    pub fn start_flow_with<F>(&mut self, flow: F, input: F::Input) -> FlowTaskId
+    where
+        F: Flow,
+        F::Output: FlowOutcome,
+    {
+        self.start_checked(flow, input, default(), F::Output::flow_error).id()
+    }
+
+    /// Start a [`Flow`] with its [`Input`](Flow::Input), whatever its 
+    /// [`Output`](Flow::Output) is. Otherwise this is the same as 
+    /// [`start_with_handle`](Self::start_with_handle)
+    pub fn start_flow_with_handle<F: Flow>(&mut self, flow: F, input: F::Input) -> FlowTaskHandle<F::Output> {
+        self.start_checked(flow, input, default(), |_| None)
+    }
+
+    /// Start a flow that was [registered](crate::app::FlowAppExt::register_flow)
+    /// as `name`, with its default value and `input`. 
+    /// 
+    /// # Errors
+    /// 
+    /// - [`FlowError::NotRegistered`] if no flow was registered as `name`
+    /// - [`FlowError::WrongInput`] if `input` isn't the [`Input`](Flow::Input) the flow takes
+    pub fn start_registered(&mut self, name: &str, input: impl Any + Send) -> Result<FlowTaskId, FlowError> {
+        let start = self.registry.as_ref()
+            .and_then(|registry| registry.get(name))
+            .ok_or_else(|| FlowError::NotRegistered(name.to_string()))?
+            .starter();
+        start(self, Box::new(input))
+    }
+
+    /// Create and start a flow task that runs on the main thread, so the future
+    /// it returns doesn't have to be [`Send`]. Otherwise this is the same as [`start`](Self::start)
+    /// 
+    /// This is for flows that hold on to things like [`Rc`](std::rc::Rc) across
+    /// `await`s. Local flows are polled each time flows are lent the [`World`],
+    /// on the main thread, so long running work in them holds up the app.
+    /// 
+    /// ```rust
+    /// # use std::{cell::Cell, rc::Rc};
+    /// # use bevy::prelude::*;
+    /// # use bevy_flow::prelude::*;
+    /// fn count_frames(mut flows: FlowTaskManager) {
+    ///     flows.start_local(|ctx: FlowContext| async move {
+    ///         let frames = Rc::new(Cell::new(0));
+    ///         while frames.get() < 10 {
+    ///             ctx.next_frame().await;
+    ///             frames.set(frames.get() + 1);
+    ///         }
+    ///     });
+    /// }
+    /// ```
+    pub fn start_local<Func, Fut, Out>(&mut self, task_fn: Func) -> FlowTaskId
+    where
+        Func: FnOnce(FlowContext) -> Fut + Send + 'static,
+        Fut: Future<Output=Out> + 'static,
+        Out: FlowOutcome,
+    {
+        self.start_runner(default(), |links, spawner, result, control| {
+            FlowTaskRunner::new_local(task_fn, links, spawner, result, control, Out::flow_error)
+        }).id()
+    }
+
+    pub(crate) fn start_checked<F: Flow>(
+        &mut self, 
+        flow: F, 
+        input: F::Input,
+        options: FlowOptions,
+        check: fn(&F::Output) -> Option<FlowError>
+    ) -> FlowTaskHandle<F::Output> {
+        self.start_runner(options, |links, spawner, result, control| {
+            FlowTaskRunner::new_with(flow, input, links, spawner, result, control, check)
+        })
+    }
+
+    /// Add the flow `runner` creates to the list
+    fn start_runner<T>(
+        &mut self, 
+        options: FlowOptions,
+        runner: impl FnOnce(FlowLinks, &FlowSpawner, Sender<Result<T, FlowError>>, Arc<FlowControl>) -> FlowTaskRunner,
+    ) -> FlowTaskHandle<T> {
+        let assets = self.assets.as_ref().map(|a| (*a).clone());
+        let handle = self.list.start_runner(FlowTaskId::next(), assets, options, runner);
         self.next.set(IsFlowing::Yes);
-        id
+        handle
     }
 
-    /// Schedule a system to run exactly once in the [`Update`] Schedule
+    /// Schedule a system to run exactly once in the [`Update`] Schedule. 
+    /// Whatever the system returns can be taken from the returned [`FlowTaskHandle`]
     /// 
     /// To ensure the it runs in the current [`Update`] cycle, schedule 
     /// the system that calls [`Self::soon`] before [`FlowTaskSystemSet`]. 
@@ -170,35 +835,52 @@ impl<'w, 's> FlowTaskManager<'w, 's> {
     /// - A [`Component`] is requested by two or more [`Query`]s and at least one
     ///   of the requests is mutable without ensuring exclusivity
     /// - Any other reason a normal bevy system will panic
-    pub fn soon<'a, Sys, Out, Params>(&mut self, system: Sys) -> FlowTaskId
+    pub fn soon<'a, Sys, Out, Params>(&mut self, system: Sys) -> FlowTaskHandle<Out>
     where
         Params: SystemParam + 'static,
-        Sys: FnOnce(Params::Item<'a, 'a>) -> Out + Send + Sync + 'static,
-        Out: Send + Sync + 'a
+        Sys: FnOnce(Params::Item<'a, 'a>) -> Out + Send + 'static,
+        Out: Send + 'static
     {
-        self.start(async |ctx: FlowContext| {
-            ctx.with::<_, _, Params>(system);
+        self.start_with_handle(async |ctx: FlowContext| {
+            ctx.with::<_, _, Params>(system).await
         })
     }
 
-    fn next_flow_task_id(&mut self) -> FlowTaskId {
-        let raw = self.list.next_id();
-        debug!("FlowTask id={raw}");
-        FlowTaskId(raw)
-    }
-
     /// Returns the number of flows currently running. When a flow finishes
     /// execution it is cleaned up, and will no longer be counted.
+    /// 
+    /// A flow that's using the [`World`] isn't counted while it does, so from 
+    /// inside a flow this doesn't include the flow itself. The same goes for 
+    /// [`get`](Self::get) and [`iter`](Self::iter).
     pub fn task_count(&self) -> usize {
         self.list.len()
     }
 
-    /// Stop all running flow tasks.
+    /// Ask a flow to stop. Returns `false` if there is no flow with that id,
+    /// which will be the case if it has already finished.
     /// 
-    /// This won't cause memory safety problems, but the threads are likely to panic.
+    /// This is cooperative: the flows next request for the [`World`] fails with
+    /// [`FlowError::Cancelled`], and it can 
+    /// check [`FlowContext::is_cancelled`] while doing long running work. Before
+    /// the flow is removed, its [`on_cancel`](FlowContext::on_cancel) hooks are run.
+    pub fn stop(&mut self, id: FlowTaskId) -> bool {
+        let Some(task) = self.list.get(&id) else { return false };
+        task.cancel();
+        true
+    }
+
+    /// Change how urgently a flow is lent the [`World`]. Returns `false` if 
+    /// there is no flow with that id, which will be the case if it has already finished.
+    pub fn set_priority(&mut self, id: FlowTaskId, priority: FlowPriority) -> bool {
+        let Some(task) = self.list.get_mut(&id) else { return false };
+        task.set_priority(priority);
+        true
+    }
+
+    /// Ask all running flow tasks to stop. See [`stop`](Self::stop)
     pub fn stop_all(&mut self) {
-        for (_id, task) in self.list.drain() {
-            drop(task);
+        for task in self.list.values() {
+            task.cancel();
         }
     }
 
@@ -239,20 +921,405 @@ impl<'w, 's> FlowTaskManager<'w, 's> {
 
 
 
-fn run_tasks(
-    world: &mut World,
-    tasks: &mut SystemState<ResMut<FlowTaskList>>,
-) {
-    // this will be safe as long as the tasks internally don't try mutating
-    // `FlowTaskList`, which they won't have access to as its private, 
-    // so this should be safe
-    let world_ref = unsafe { &mut *(world as *mut _) };
+/// Creates a system that lends the `World` to the flows that run in `schedule`
+fn run_tasks_in(schedule: InternedScheduleLabel) -> impl FnMut(&mut World) {
+    move |world: &mut World| run_tasks(world, schedule)
+}
+
+/// If `flow` should be lent the `World` while `schedule` is running
+fn runs_in(flow: &FlowTaskRunner, schedule: InternedScheduleLabel, defaults: &[InternedScheduleLabel]) -> bool {
+    // cancelled flows are wrapped up wherever they are
+    if flow.is_cancelled() { return true }
+    match flow.schedule() {
+        Some(pinned) => pinned == schedule,
+        None => defaults.contains(&schedule),
+    }
+}
+
+fn run_tasks(world: &mut World, schedule: InternedScheduleLabel) {
+    let Some(mut list) = world.get_resource_mut::<FlowTaskList>() else { return };
+    let list = &mut *list;
+    let defaults = list.default_schedules.clone();
+    let order = list.queue.order(&list.tasks);
+    let commands = list.commands.clone();
+    commands.apply(world);
+
+    // flows that have left their state are wrapped up before anything else happens
+    for id in &order {
+        let Some(task) = world.resource_mut::<FlowTaskList>().tasks.remove(id) else { continue };
+        task.check_scope(world);
+        world.resource_mut::<FlowTaskList>().tasks.insert(*id, task);
+    }
+
+    // local flows can only be polled from here, so they get a chance to make
+    // progress, and ask for the `World`, before the loans start
+    let list = world.resource::<FlowTaskList>();
+    let has_local = list.tasks.values().any(FlowTaskRunner::is_local);
+    let mut local = match has_local {
+        true => world.remove_non_send_resource::<LocalFlows>().unwrap_or_default(),
+        false => LocalFlows::default(),
+    };
+    local.start(&world.resource::<FlowTaskList>().tasks);
+    local.poll_all();
+
+    let mut waiting = 0;
+    for id in order {
+        let list = world.resource::<FlowTaskList>();
+        let Some(task) = list.tasks.get(&id) else { continue };
+        if !task.wants_world() || !runs_in(task, schedule, &defaults) { continue }
+        // cancelled flows are wrapped up straight away, whatever the budget
+        if !task.is_cancelled() && !list.queue.can_lend(id, task.priority()) {
+            waiting += 1;
+            continue
+        }
+
+        // only the flow being lent the `World` is taken out of the list, so 
+        // the rest can still be looked up, and stopped, from inside it
+        let mut list = world.resource_mut::<FlowTaskList>();
+        let Some(mut task) = list.tasks.remove(&id) else { continue };
+        let priority = task.priority();
+        let mut loans = 1;
+        let mut start = Instant::now();
+        // whatever the flow queued has to land before it sees the `World` again
+        commands.apply(world);
+        let mut finished = task.lend(world, local.get_mut(id));
+        loop {
+            // waiting for the flow to ask again holds up the app too, so it
+            // counts against the budget
+            let mut list = world.resource_mut::<FlowTaskList>();
+            list.queue.lent(id, priority, start.elapsed());
+            start = Instant::now();
+            if finished { break }
+
+            let Some(linger) = list.queue.can_lend_again(id, priority, loans) else { break };
+            local.poll(id);
+            if !task.asks_again_within(linger) {
+                world.resource_mut::<FlowTaskList>().queue.spend(start.elapsed());
+                break
+            }
+
+            loans += 1;
+            commands.apply(world);
+            finished = task.lend(world, local.get_mut(id));
+        }
+        world.resource_mut::<FlowTaskList>().tasks.insert(id, task);
+    }
+    if waiting > 0 {
+        trace!("{waiting} flows are waiting on the loan budget, or their background interval");
+    }
+
+    // a flow cancelled after its turn might finish before the next one, 
+    // and its hooks still need to run before it's removed
+    let cancelled = world.resource::<FlowTaskList>().tasks.iter()
+        .filter(|(_, task)| task.is_cancelled() && task.is_finished())
+        .map(|(id, _)| *id)
+        .collect::<Vec<_>>();
+    for id in cancelled {
+        let Some(task) = world.resource_mut::<FlowTaskList>().tasks.remove(&id) else { continue };
+        task.run_cancel_hooks(world);
+        world.resource_mut::<FlowTaskList>().tasks.insert(id, task);
+    }
+
+    let mut list = world.resource_mut::<FlowTaskList>();
+    let (failed, panicked) = list.clean();
+    if has_local {
+        local.retain(&list.tasks);
+        world.insert_non_send_resource(local);
+    }
+    if !failed.is_empty() {
+        world.send_event_batch(failed);
+    }
+    if !panicked.is_empty() {
+        world.send_event_batch(panicked);
+    }
+}
+
+/// Gives the loan budget back at the start of each frame, and moves on the
+/// frame count flows wait by
+fn start_loan_frame(list: Option<ResMut<FlowTaskList>>) {
+    if let Some(mut list) = list {
+        list.queue.start_frame();
+        list.now.next_frame();
+    }
+}
+
+/// Creates a system that adds loan systems to schedules flows are pinned to,
+/// but which don't have one yet. `running` is the schedule the system is in,
+/// which can't be changed until it's done.
+fn install_loan_systems(running: InternedScheduleLabel) -> impl FnMut(&mut World) {
+    move |world: &mut World| {
+        let Some(list) = world.get_resource::<FlowTaskList>() else { return };
+        let missing = list.values()
+            .filter_map(FlowTaskRunner::schedule)
+            .filter(|schedule| *schedule != running && !list.installed.contains(schedule))
+            .collect::<HashSet<_>>();
+
+        for schedule in missing {
+            debug!("Lending the World to flows in {schedule:?}");
+            world.resource_mut::<Schedules>()
+                .add_systems(schedule, run_tasks_in(schedule).in_set(FlowTaskSystemSet));
+            world.resource_mut::<FlowTaskList>().installed.insert(schedule);
+        }
+    }
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use std::{thread::{current, sleep}, time::Duration};
+
+    use bevy::{core::FrameCount, ecs::{schedule::ScheduleLabel, system::SystemState}, prelude::*};
+
+    use crate::prelude::*;
+
+    use super::{FlowTaskId, FlowTaskList};
+
+    #[derive(Resource)]
+    struct Victim(FlowTaskId);
+
+    #[derive(Resource, Default)]
+    struct Stopped {
+        found: bool,
+        hooks_ran: bool,
+    }
+
+    #[test]
+    fn flows_can_stop_other_flows() {
+        let mut app = App::new();
+        app.add_plugins((MinimalPlugins, FlowTasksPlugin::default()))
+            .init_resource::<Stopped>()
+            .add_systems(Startup, |mut commands: Commands, mut flows: FlowTaskManager| {
+                let victim = flows.start_local::<_, _, ()>(|ctx: FlowContext| async move {
+                    ctx.on_cancel(|world| world.resource_mut::<Stopped>().hooks_ran = true);
+                    loop { ctx.next_frame().await }
+                });
+                commands.insert_resource(Victim(victim));
+
+                flows.start_local(|ctx: FlowContext| async move {
+                    ctx.next_frame().await;
+                    ctx.with_world(|world| {
+                        let victim = world.resource::<Victim>().0;
+                        let mut state = SystemState::<FlowTaskManager>::new(world);
+                        let found = state.get_mut(world).stop(victim);
+                        state.apply(world);
+                        world.resource_mut::<Stopped>().found = found;
+                    }).await;
+                });
+            });
+
+        for _ in 0..5 {
+            app.update();
+        }
+
+        let stopped = app.world().resource::<Stopped>();
+        assert!(stopped.found);
+        assert!(stopped.hooks_ran);
+        assert_eq!(app.world().resource::<FlowTaskList>().len(), 0);
+    }
+
+    #[derive(Resource, Default)]
+    struct ThreadNames(Vec<String>);
+
+    #[test]
+    fn flow_threads_are_numbered() {
+        let mut app = App::new();
+        app.add_plugins((MinimalPlugins, FlowTasksPlugin::default().with_threads("mover", None)))
+            .init_resource::<ThreadNames>()
+            .add_systems(Startup, |mut flows: FlowTaskManager| {
+                for _ in 0..2 {
+                    flows.start(|ctx: FlowContext| async move {
+                        let name = current().name().unwrap_or_default().to_string();
+                        ctx.with_world(|world| world.resource_mut::<ThreadNames>().0.push(name)).await;
+                    });
+                }
+            });
+
+        // flows on other threads take a moment to ask for the `World`
+        for _ in 0..200 {
+            app.update();
+            if app.world().resource::<ThreadNames>().0.len() == 2 { break }
+            sleep(Duration::from_millis(1));
+        }
+
+        let mut names = app.world().resource::<ThreadNames>().0.clone();
+        names.sort();
+        assert_eq!(names, ["mover-1", "mover-2"]);
+    }
+
+    /// How many systems in `schedule` lend the `World` to flows
+    fn loan_systems(app: &App, schedule: impl ScheduleLabel) -> usize {
+        let Some(schedule) = app.get_schedule(schedule) else { return 0 };
+        schedule.systems().expect("the app has been updated")
+            .filter(|(_, system)| system.name().contains("run_tasks_in"))
+            .count()
+    }
+
+    #[test]
+    fn configured_schedules_replace_update() {
+        let mut app = App::new();
+        app.add_plugins((MinimalPlugins, FlowTasksPlugin::default().in_schedules([PostUpdate, PostUpdate])));
+        app.update();
+
+        assert_eq!(loan_systems(&app, Update), 0);
+        assert_eq!(loan_systems(&app, PostUpdate), 1);
+    }
+
+    #[test]
+    fn pinned_flows_share_loan_systems() {
+        let mut app = App::new();
+        app.add_plugins((MinimalPlugins, FlowTasksPlugin::default()))
+            .add_systems(Startup, |mut flows: FlowTaskManager| {
+                for _ in 0..2 {
+                    flows.start_in(Update, |ctx: FlowContext| async move { ctx.wait_frames(10).await });
+                    flows.start_in(PostUpdate, |ctx: FlowContext| async move { ctx.wait_frames(10).await });
+                }
+            });
+        for _ in 0..3 {
+            app.update();
+        }
 
-    let mut tasks = tasks.get_mut(world_ref);
-    for (_id, task) in tasks.iter_mut() {
-        task.loan_world(world);
+        assert_eq!(loan_systems(&app, Update), 1);
+        assert_eq!(loan_systems(&app, PostUpdate), 1);
     }
 
-    tasks.clean();
-    // if ! done.is_empty() { println!("ALL TASK DONE!!!") }
-}
\ No newline at end of file
+    /// Starts a local flow with `priority`, that asks for the `World` once and
+    /// then waits forever, and stops it in the third frame
+    fn stop_waiting_flow(plugin: FlowTasksPlugin, priority: FlowPriority) -> App {
+        let mut app = App::new();
+        app.add_plugins((MinimalPlugins, plugin))
+            .init_resource::<Stopped>()
+            .add_systems(Startup, move |mut commands: Commands, mut flows: FlowTaskManager| {
+                let id = flows.start_local::<_, _, ()>(|ctx: FlowContext| async move {
+                    ctx.on_cancel(|world| world.resource_mut::<Stopped>().hooks_ran = true);
+                    ctx.with_world(|_| ()).await;
+                    loop { ctx.next_frame().await }
+                });
+                flows.set_priority(id, priority);
+                commands.insert_resource(Victim(id));
+            })
+            .add_systems(Update, (|mut flows: FlowTaskManager, victim: Res<Victim>, frame: Res<FrameCount>| {
+                if frame.0 == 2 { flows.stop(victim.0); }
+            }).before(FlowTaskSystemSet));
+
+        for _ in 0..6 {
+            app.update();
+        }
+        app
+    }
+
+    #[test]
+    fn cancelled_background_flows_skip_their_interval() {
+        let plugin = FlowTasksPlugin::default().with_background_interval(100);
+        let app = stop_waiting_flow(plugin, FlowPriority::Background);
+
+        assert!(app.world().resource::<Stopped>().hooks_ran);
+        assert_eq!(app.world().resource::<FlowTaskList>().len(), 0);
+    }
+
+    #[test]
+    fn cancelled_flows_skip_the_loan_budget() {
+        let plugin = FlowTasksPlugin::default().with_loan_budget(Duration::ZERO);
+        let app = stop_waiting_flow(plugin, FlowPriority::Normal);
+
+        assert!(app.world().resource::<Stopped>().hooks_ran);
+        assert_eq!(app.world().resource::<FlowTaskList>().len(), 0);
+    }
+
+    #[derive(Resource, Default)]
+    struct Autosaves(Vec<FlowTaskId>);
+
+    #[test]
+    fn unique_flows_can_start_again_once_finished() {
+        let mut app = App::new();
+        app.add_plugins((MinimalPlugins, FlowTasksPlugin::default()))
+            .init_resource::<Autosaves>()
+            .add_systems(Update, (|mut flows: FlowTaskManager, mut autosaves: ResMut<Autosaves>, frame: Res<FrameCount>| {
+                if ![0, 1, 30].contains(&frame.0) { return }
+                let id = flows.start_unique("autosave", |ctx: FlowContext| async move { ctx.wait_frames(2).await });
+                autosaves.0.push(id);
+            }).before(FlowTaskSystemSet));
+
+        // the flow runs on its own thread, so give it plenty of time to finish
+        for _ in 0..31 {
+            app.update();
+            sleep(Duration::from_millis(1));
+        }
+
+        let autosaves = &app.world().resource::<Autosaves>().0;
+        assert_eq!(autosaves.len(), 3);
+        assert_eq!(autosaves[0], autosaves[1], "refused while the first autosave runs");
+        assert_ne!(autosaves[0], autosaves[2], "accepted once it has finished");
+    }
+
+    #[derive(Resource, Default)]
+    struct Counter(usize);
+
+    /// Runs a few flows that each borrow the `World` three times on `executor`,
+    /// until they've all finished
+    fn run_flows_on(executor: FlowExecutor) -> App {
+        let mut app = App::new();
+        app.add_plugins((MinimalPlugins, FlowTasksPlugin::default().with_executor(executor)))
+            .init_resource::<Counter>()
+            .add_systems(Startup, |mut flows: FlowTaskManager| {
+                for _ in 0..4 {
+                    flows.start(|ctx: FlowContext| async move {
+                        for _ in 0..3 {
+                            ctx.with_world(|world| world.resource_mut::<Counter>().0 += 1).await;
+                            ctx.next_frame().await;
+                        }
+                    });
+                }
+            });
+
+        // pooled flows take a moment to be polled
+        for _ in 0..500 {
+            app.update();
+            if app.world().resource::<FlowTaskList>().is_empty() { break }
+            sleep(Duration::from_millis(1));
+        }
+        app
+    }
+
+    #[test]
+    fn flows_finish_on_pools() {
+        for executor in [FlowExecutor::AsyncComputePool, FlowExecutor::DedicatedPool { threads: 2 }] {
+            let app = run_flows_on(executor);
+            assert_eq!(app.world().resource::<Counter>().0, 12);
+            assert_eq!(app.world().resource::<FlowTaskList>().len(), 0);
+        }
+    }
+
+    #[derive(Resource, Default)]
+    struct Panics(Vec<String>);
+
+    fn explode() {
+        panic!("sync panic");
+    }
+
+    #[test]
+    fn panics_before_a_flow_returns_its_future_are_caught() {
+        let mut app = App::new();
+        app.add_plugins((MinimalPlugins, FlowTasksPlugin::default()))
+            .init_resource::<Panics>()
+            .add_systems(Startup, |mut flows: FlowTaskManager| {
+                flows.start(|_ctx| { explode(); async {} });
+                flows.start_local(|_ctx| { explode(); async {} });
+            })
+            .add_systems(Last, |mut panicked: EventReader<FlowPanicked>, mut panics: ResMut<Panics>| {
+                panics.0.extend(panicked.read().map(|evt| evt.message.clone()));
+            });
+
+        // the thread flow takes a moment to finish
+        for _ in 0..200 {
+            app.update();
+            if app.world().resource::<Panics>().0.len() == 2 { break }
+            sleep(Duration::from_millis(1));
+        }
+
+        let panics = &app.world().resource::<Panics>().0;
+        assert_eq!(panics.len(), 2);
+        assert!(panics.iter().all(|message| message.contains("sync panic")));
+        assert_eq!(app.world().resource::<FlowTaskList>().len(), 0);
+    }
+}
@@ -0,0 +1,116 @@
+//! Flows registered on the [`App`], so they can be found
+//! and started by name
+
+use std::{any::{type_name, Any}, borrow::Cow};
+
+use bevy::{prelude::*, utils::hashbrown::HashMap};
+
+use crate::{error::{FlowError, FlowOutcome}, flow::Flow, plugin::FlowTaskManager, runner::FlowTaskId};
+
+
+/// Starts a registered flow, with an input that hasn't been checked yet
+type StartRegistered = fn(&mut FlowTaskManager<'_, '_>, Box<dyn Any + Send>) -> Result<FlowTaskId, FlowError>;
+
+/// A [`Flow`] type that was registered with
+/// [`FlowAppExt::register_flow`](crate::app::FlowAppExt::register_flow)
+#[derive(Clone, Debug)]
+pub struct RegisteredFlow {
+    name: Cow<'static, str>,
+    type_name: &'static str,
+    input: &'static str,
+    output: &'static str,
+    start: StartRegistered,
+}
+
+impl RegisteredFlow {
+    /// The name of the flow. See [`Flow::name`]
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The type name of the flow
+    pub fn type_name(&self) -> &'static str {
+        self.type_name
+    }
+
+    /// The type name of the flows [`Input`](Flow::Input)
+    pub fn input_type_name(&self) -> &'static str {
+        self.input
+    }
+
+    /// The type name of the flows [`Output`](Flow::Output)
+    pub fn output_type_name(&self) -> &'static str {
+        self.output
+    }
+
+    pub(crate) fn starter(&self) -> StartRegistered {
+        self.start
+    }
+}
+
+/// Every [`Flow`] type registered with
+/// [`FlowAppExt::register_flow`](crate::app::FlowAppExt::register_flow), by name.
+///
+/// Registered flows are started by name with
+/// [`FlowTaskManager::start_registered`]
+#[derive(Resource, Default)]
+pub struct FlowRegistry {
+    flows: HashMap<Cow<'static, str>, RegisteredFlow>,
+}
+
+impl FlowRegistry {
+    /// Add `F`, under the name its default value has. A flow that was
+    /// registered under the same name is replaced
+    pub(crate) fn register<F>(&mut self)
+    where
+        F: Flow + Default,
+        F::Output: FlowOutcome,
+    {
+        let name = F::default().name();
+        let flow = RegisteredFlow {
+            name: name.clone(),
+            type_name: type_name::<F>(),
+            input: type_name::<F::Input>(),
+            output: type_name::<F::Output>(),
+            start: start_registered::<F>,
+        };
+        if let Some(old) = self.flows.insert(name, flow) {
+            warn!("Flow {} replaced {} in the FlowRegistry", type_name::<F>(), old.type_name);
+        }
+    }
+
+    /// Get a flow by name
+    pub fn get(&self, name: &str) -> Option<&RegisteredFlow> {
+        self.flows.get(name)
+    }
+
+    /// Returns `true` if a flow was registered as `name`
+    pub fn contains(&self, name: &str) -> bool {
+        self.flows.contains_key(name)
+    }
+
+    /// Returns an Iterator of all of the registered flows
+    pub fn iter(&self) -> impl Iterator<Item = &RegisteredFlow> {
+        self.flows.values()
+    }
+
+    /// Returns the number of registered flows
+    pub fn len(&self) -> usize {
+        self.flows.len()
+    }
+
+    /// Returns `true` if no flows have been registered
+    pub fn is_empty(&self) -> bool {
+        self.flows.is_empty()
+    }
+}
+
+fn start_registered<F>(flows: &mut FlowTaskManager<'_, '_>, input: Box<dyn Any + Send>) -> Result<FlowTaskId, FlowError>
+where
+    F: Flow + Default,
+    F::Output: FlowOutcome,
+{
+    let input = input.downcast::<F::Input>()
+        .map_err(|_| FlowError::WrongInput(type_name::<F::Input>()))?;
+    Ok(flows.start_flow_with(F::default(), *input))
+}
@@ -1,22 +1,368 @@
-//! 
+//! Drives a single flow, and hands it the [`World`] when it asks for it
 
-use std::{future::Future, thread::{JoinHandle, spawn}};
+use std::{
+    any::type_name,
+    borrow::Cow,
+    future::{poll_fn, Future}, 
+    mem::take, 
+    panic::{catch_unwind, AssertUnwindSafe}, 
+    pin::Pin,
+    sync::{atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering}, Arc, Mutex, Weak}, 
+    task::{Context, Poll, Waker},
+    thread::{yield_now, Builder, JoinHandle},
+    time::Duration,
+};
 
-use bevy::{prelude::*, tasks::futures_lite::future::block_on};
-use async_channel::{bounded, Receiver, Sender};
+use bevy::{
+    ecs::schedule::InternedScheduleLabel,
+    prelude::*, 
+    utils::{hashbrown::HashMap, Instant},
+    tasks::{futures_lite::{future::block_on, FutureExt}, AsyncComputeTaskPool, Task, TaskPool, TaskPoolBuilder}
+};
+use async_channel::{bounded, unbounded, Receiver, Sender, TryRecvError};
 
-use crate::context::FlowContext;
+use crate::{commands::FlowCommand, context::FlowContext, error::FlowError, flow::Flow, scheduler::FlowPriority, waiter::{FlowNow, WaitCheck}};
 
 
-/// A unique id to track a 
+/// A unique id to track a flow task
 #[derive(Clone, Copy, Debug, Hash, PartialEq, Eq)]
 pub struct FlowTaskId(pub(crate) u64);
 
+impl FlowTaskId {
+    /// A new id. Ids are handed out before their flow is started, so flows 
+    /// started through [`Commands`] have one straight away
+    pub(crate) fn next() -> Self {
+        static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+        Self(NEXT_ID.fetch_add(1, Ordering::Relaxed))
+    }
+}
+
+/// Selects where the futures of flows are polled.
+/// 
+/// Set this through [`FlowTasksPlugin::with_executor`](crate::plugin::FlowTasksPlugin::with_executor).
+/// Threads are named with [`FlowTasksPlugin::thread_prefix`](crate::plugin::FlowTasksPlugin::thread_prefix)
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub enum FlowExecutor {
+    /// Every flow gets its own OS thread, which blocks on the flow until it 
+    /// finishes. Threads are expensive, so only use this for a handful of flows.
+    #[default]
+    Thread,
+    /// Flows are polled on bevy's [`AsyncComputeTaskPool`], and only occupy a
+    /// thread while they are actually doing work. Suited to running hundreds 
+    /// of small flows at once.
+    AsyncComputePool,
+    /// Flows are polled on a [`TaskPool`] owned by the plugin, so they can't
+    /// starve other users of the [`AsyncComputeTaskPool`].
+    DedicatedPool {
+        /// How many threads the pool spawns
+        threads: usize,
+    },
+}
+
+/// Everything needed to start new flows, resolved from the 
+/// [`FlowTasksPlugin`](crate::plugin::FlowTasksPlugin) settings
+#[derive(Clone)]
+pub(crate) struct FlowSpawner {
+    pool: SpawnPool,
+    /// How many messages can be in flight between a flow and its runner
+    capacity: usize,
+    thread_prefix: String,
+    stack_size: Option<usize>,
+    /// How many threads have been spawned for flows, to number the next one
+    threads: Arc<AtomicU64>,
+}
+
+/// The resolved form of a [`FlowExecutor`]
+#[derive(Clone)]
+enum SpawnPool {
+    Thread,
+    AsyncCompute,
+    Dedicated(Arc<TaskPool>),
+}
+
+impl FlowSpawner {
+    pub(crate) fn new(
+        executor: &FlowExecutor, 
+        capacity: usize, 
+        thread_prefix: &str, 
+        stack_size: Option<usize>,
+    ) -> Self {
+        let pool = match executor {
+            FlowExecutor::Thread => SpawnPool::Thread,
+            FlowExecutor::AsyncComputePool => SpawnPool::AsyncCompute,
+            FlowExecutor::DedicatedPool { threads } => {
+                let mut pool = TaskPoolBuilder::new()
+                    .num_threads(*threads)
+                    .thread_name(thread_prefix.to_string());
+                if let Some(stack_size) = stack_size {
+                    pool = pool.stack_size(stack_size);
+                }
+                SpawnPool::Dedicated(Arc::new(pool.build()))
+            }
+        };
+
+        Self {
+            pool,
+            capacity: capacity.max(1),
+            thread_prefix: thread_prefix.to_string(),
+            stack_size,
+            threads: default(),
+        }
+    }
+
+    fn spawn(&self, flow: impl Future<Output = ()> + Send + 'static) -> RunnerTask {
+        match &self.pool {
+            SpawnPool::Thread => {
+                let n = self.threads.fetch_add(1, Ordering::Relaxed) + 1;
+                let mut thread = Builder::new().name(format!("{}-{n}", self.thread_prefix));
+                if let Some(stack_size) = self.stack_size {
+                    thread = thread.stack_size(stack_size);
+                }
+                let thread = thread.spawn(move || block_on(flow))
+                    .expect("failed to spawn a thread for a flow");
+                RunnerTask::Thread(thread)
+            },
+            SpawnPool::AsyncCompute => {
+                let pool = AsyncComputeTaskPool::get_or_init(TaskPool::new);
+                RunnerTask::Pool(pool.spawn(flow))
+            },
+            SpawnPool::Dedicated(pool) => RunnerTask::Pool(pool.spawn(flow)),
+        }
+    }
+}
+
+impl Default for FlowSpawner {
+    fn default() -> Self {
+        Self::new(&FlowExecutor::Thread, 5, "flow", None)
+    }
+}
+
 /// Manages the execution of a flow task
 pub struct FlowTaskRunner {
     send: Sender<LTMsg>,
     recv: Receiver<LTResult>,
-    task: JoinHandle<()>
+    /// The flows end of `send`, for taking back a `World` nobody is waiting for
+    reclaim: Receiver<LTMsg>,
+    /// Requests for the `World` that haven't been answered or withdrawn
+    pending: usize,
+    task: RunnerTask,
+    control: Arc<FlowControl>,
+    failure: Option<FlowError>,
+    name: Cow<'static, str>,
+    priority: FlowPriority,
+}
+
+/// Everything a new flow needs from the app, apart from its own function
+#[derive(Clone)]
+pub(crate) struct FlowLinks {
+    pub(crate) assets: Option<AssetServer>,
+    pub(crate) waiters: Sender<WaitCheck>,
+    pub(crate) commands: Sender<FlowCommand>,
+    pub(crate) now: Arc<FlowNow>,
+}
+
+/// A closure registered with [`FlowContext::on_cancel`]
+pub(crate) type CancelHook = Box<dyn FnOnce(&mut World) + Send>;
+
+/// Decides if a flow should keep running, like while the app hasn't left a state.
+/// See [`FlowTaskManager::start_scoped`](crate::plugin::FlowTaskManager::start_scoped)
+pub(crate) type FlowScope = Box<dyn FnMut(&World) -> bool + Send>;
+
+/// State shared between a flow, its runner, and its handles
+#[derive(Default)]
+pub(crate) struct FlowControl {
+    cancelled: AtomicBool,
+    /// See [`is_finished`](Self::is_finished)
+    finished: AtomicBool,
+    /// Requests for the `World` that were withdrawn while the channel to the
+    /// runner was full. See [`withdraw`](Self::withdraw)
+    withdrawn: AtomicUsize,
+    on_cancel: Mutex<Vec<CancelHook>>,
+    /// The schedule the flow is pinned to, if any
+    schedule: Mutex<Option<InternedScheduleLabel>>,
+    /// The entity the flow belongs to, if any. See [`FlowOwner`](crate::entity::FlowOwner)
+    owner: Option<Entity>,
+    scope: Mutex<Option<FlowScope>>,
+    /// Flows started with [`FlowContext::spawn_child`], which are cancelled along with this one
+    children: Mutex<Vec<Weak<FlowControl>>>,
+}
+
+impl FlowControl {
+    pub(crate) fn pinned_to(schedule: Option<InternedScheduleLabel>) -> Self {
+        Self { schedule: Mutex::new(schedule), ..default() }
+    }
+
+    pub(crate) fn owned_by(self, owner: Option<Entity>) -> Self {
+        Self { owner, ..self }
+    }
+
+    pub(crate) fn owner(&self) -> Option<Entity> {
+        self.owner
+    }
+
+    pub(crate) fn scoped_to(self, scope: Option<FlowScope>) -> Self {
+        Self { scope: Mutex::new(scope), ..self }
+    }
+
+    /// Returns `false` once the flow has left its scope. Flows without one never do
+    fn in_scope(&self, world: &World) -> bool {
+        match &mut *self.scope.lock().unwrap_or_else(|e| e.into_inner()) {
+            Some(scope) => scope(world),
+            None => true,
+        }
+    }
+
+    pub(crate) fn schedule(&self) -> Option<InternedScheduleLabel> {
+        *self.schedule.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    pub(crate) fn set_schedule(&self, schedule: Option<InternedScheduleLabel>) {
+        *self.schedule.lock().unwrap_or_else(|e| e.into_inner()) = schedule;
+    }
+
+    pub(crate) fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Release);
+        self.cancel_children();
+    }
+
+    /// Cancel every child flow that's still around
+    pub(crate) fn cancel_children(&self) {
+        let children = take(&mut *self.children.lock().unwrap_or_else(|e| e.into_inner()));
+        for child in children.iter().filter_map(Weak::upgrade) {
+            child.cancel();
+        }
+    }
+
+    /// Make `child` a child of this flow, so it's cancelled along with it
+    pub(crate) fn adopt(&self, child: &Arc<FlowControl>) {
+        let mut children = self.children.lock().unwrap_or_else(|e| e.into_inner());
+        children.retain(|child| child.strong_count() > 0);
+        children.push(Arc::downgrade(child));
+        drop(children);
+
+        // the flow may have been cancelled while the child was being added
+        if self.is_cancelled() {
+            self.cancel_children();
+        }
+    }
+
+    pub(crate) fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Acquire)
+    }
+
+    /// Returns `true` once the flow has finished, and been removed from the
+    /// [`FlowTaskList`](crate::plugin::FlowTaskList). 
+    /// 
+    /// Handles, names, keys and owners all hold on to the control of flows 
+    /// they remember, so whether anything else holds it says nothing about 
+    /// the flow. This is what they check to tell when to forget it.
+    pub(crate) fn is_finished(&self) -> bool {
+        self.finished.load(Ordering::Acquire)
+    }
+
+    /// Withdraw a request for the `World` without going through the channel
+    /// to the runner, for when it's full. Flows can't wait for room, as the 
+    /// runner only makes some while it's lending to them, and waiting would 
+    /// hold up whatever thread the flow is being dropped on.
+    pub(crate) fn withdraw(&self) {
+        self.withdrawn.fetch_add(1, Ordering::AcqRel);
+    }
+
+    fn take_withdrawn(&self) -> usize {
+        self.withdrawn.swap(0, Ordering::AcqRel)
+    }
+
+    pub(crate) fn add_cancel_hook(&self, hook: CancelHook) {
+        self.on_cancel.lock().unwrap_or_else(|e| e.into_inner()).push(hook);
+    }
+
+    /// Run all the cancel hooks that haven't already been run. Panicking
+    /// hooks are logged, and don't stop the other hooks from running
+    fn run_cancel_hooks(&self, world: &mut World, name: &str) {
+        let hooks = take(&mut *self.on_cancel.lock().unwrap_or_else(|e| e.into_inner()));
+        for hook in hooks {
+            if let Err(payload) = catch_unwind(AssertUnwindSafe(|| hook(world))) {
+                let err = FlowError::from_panic(payload);
+                error!("Cancel hook of flow {name} failed: {err}");
+            }
+        }
+    }
+}
+
+/// Whatever is polling the flows future. Dropping a [`Task`] cancels it, 
+/// while dropping a [`JoinHandle`] detaches the thread.
+enum RunnerTask {
+    Thread(JoinHandle<()>),
+    Pool(Task<()>),
+    /// Polled on the main thread by [`LocalFlows`]. `start` is taken once
+    /// the future has been created there
+    Local {
+        start: Mutex<Option<LocalStart>>,
+        finished: Arc<AtomicBool>,
+    },
+}
+
+/// The future of a flow that runs on the main thread, which doesn't have to be [`Send`]
+type LocalFuture = Pin<Box<dyn Future<Output = ()>>>;
+
+/// Creates the future of a flow that runs on the main thread. Flows can be 
+/// started from any thread, so this is sent to the main thread first
+type LocalStart = Box<dyn FnOnce() -> LocalFuture + Send>;
+
+/// A flow that runs on the main thread. This is empty once it has finished,
+/// so it's never polled again
+pub(crate) struct LocalFlow(Option<LocalFuture>);
+
+impl LocalFlow {
+    fn poll(&mut self, cx: &mut Context) {
+        let Some(future) = &mut self.0 else { return };
+        if future.as_mut().poll(cx).is_ready() {
+            self.0 = None;
+        }
+    }
+}
+
+/// The futures of flows started with [`FlowTaskManager::start_local`](crate::plugin::FlowTaskManager::start_local).
+/// They can't leave the main thread, so this is kept as a non-send resource
+#[derive(Default)]
+pub(crate) struct LocalFlows {
+    flows: HashMap<FlowTaskId, LocalFlow>,
+}
+
+impl LocalFlows {
+    /// Create the futures of local flows started since last time
+    pub(crate) fn start(&mut self, tasks: &HashMap<FlowTaskId, FlowTaskRunner>) {
+        for (id, task) in tasks {
+            if let Some(start) = task.take_local_start() {
+                self.flows.insert(*id, LocalFlow(Some(start())));
+            }
+        }
+    }
+
+    /// Poll a local flow once, so it can get on with whatever it's doing. Nothing
+    /// wakes local flows, they're just polled each time flows are lent the `World`
+    pub(crate) fn poll(&mut self, id: FlowTaskId) {
+        if let Some(flow) = self.flows.get_mut(&id) {
+            flow.poll(&mut Context::from_waker(Waker::noop()));
+        }
+    }
+
+    /// Poll every local flow once. See [`poll`](Self::poll)
+    pub(crate) fn poll_all(&mut self) {
+        let mut cx = Context::from_waker(Waker::noop());
+        for flow in self.flows.values_mut() {
+            flow.poll(&mut cx);
+        }
+    }
+
+    pub(crate) fn get_mut(&mut self, id: FlowTaskId) -> Option<&mut LocalFlow> {
+        self.flows.get_mut(&id)
+    }
+
+    /// Drop finished flows, and flows that are no longer in `tasks`
+    pub(crate) fn retain(&mut self, tasks: &HashMap<FlowTaskId, FlowTaskRunner>) {
+        self.flows.retain(|id, flow| flow.0.is_some() && tasks.contains_key(id));
+    }
 }
 
 // unsafe impl Send for FlowTaskRunner { }
@@ -24,72 +370,303 @@ pub struct FlowTaskRunner {
 
 impl FlowTaskRunner {
 
-    /// Start a new long running task. It will start immediatly
-    pub fn new<Func, Fut>(task_fn: Func, assets: Option<AssetServer>) -> Self 
+    /// Start a new long running task on its own thread. It will start immediatly
+    pub fn new(flow: impl Flow<Input=(), Output=()>, assets: Option<AssetServer>) -> Self {
+        let (result, _) = bounded(1);
+        // without the plugin, nothing checks on waiting flows, or applies commands
+        let (waiters, _) = unbounded();
+        let (commands, _) = unbounded();
+        let links = FlowLinks { assets, waiters, commands, now: default() };
+        Self::new_with(flow, (), links, &default(), result, default(), |_| None)
+    }
+
+    /// Start a new flow. `check` decides if what the flow returned
+    /// means it has failed
+    pub(crate) fn new_with<F: Flow>(
+        flow: F, 
+        input: F::Input,
+        links: FlowLinks, 
+        spawner: &FlowSpawner,
+        result: Sender<Result<F::Output, FlowError>>,
+        control: Arc<FlowControl>,
+        check: fn(&F::Output) -> Option<FlowError>,
+    ) -> Self {
+        Self::build(flow.name(), links, spawner, control, |ctx, done| {
+            spawner.spawn(report(move || flow.run(ctx, input), result, check, done))
+        })
+    }
+
+    /// Start a new flow that runs on the main thread, so its future doesn't 
+    /// need to be [`Send`]. It's polled by the plugin, using [`LocalFlows`]
+    pub(crate) fn new_local<Func, Fut>(
+        task_fn: Func, 
+        links: FlowLinks, 
+        spawner: &FlowSpawner,
+        result: Sender<Result<Fut::Output, FlowError>>,
+        control: Arc<FlowControl>,
+        check: fn(&Fut::Output) -> Option<FlowError>,
+    ) -> Self 
     where
-        Func: FnOnce(FlowContext) -> Fut + Send + Sync + 'static,
-        Fut: Future<Output=()> + Send + Sync,
+        Func: FnOnce(FlowContext) -> Fut + Send + 'static,
+        Fut: Future + 'static,
+        Fut::Output: Send + 'static,
     {
-        let (send, recv_far) = bounded(5);
-        let (send_far, recv) = bounded(5);
-            
-        let task = spawn(move || {
-            block_on(async {
-                let send_done = send_far.clone();
-                let tasker = FlowContext::new(send_far, recv_far, assets);
-                task_fn(tasker).await;
-
-                send_done.send(LTResult::Finished).await.unwrap();
-            });
-        });
+        Self::build(type_name::<Func>().into(), links, spawner, control, |ctx, done| {
+            let finished = Arc::new(AtomicBool::new(false));
+            let flag = finished.clone();
+            let start: LocalStart = Box::new(move || Box::pin(async move {
+                report(move || task_fn(ctx), result, check, done).await;
+                flag.store(true, Ordering::Release);
+            }));
+            RunnerTask::Local { start: Mutex::new(Some(start)), finished }
+        })
+    }
+
+    /// Set up the channels between a flow and its runner, and hand the flows 
+    /// end of them to `spawn`, which gets the flow going
+    fn build(
+        name: Cow<'static, str>,
+        links: FlowLinks, 
+        spawner: &FlowSpawner,
+        control: Arc<FlowControl>,
+        spawn: impl FnOnce(FlowContext, Sender<LTResult>) -> RunnerTask,
+    ) -> Self {
+        let (send, recv_far) = bounded(spawner.capacity);
+        let (send_far, recv) = bounded(spawner.capacity);
+        let reclaim = recv_far.clone();
+
+        // the flow only ever waits on the channels, so the world-loan handshake
+        // is what wakes it up again, no matter what is polling it
+        let ctx = FlowContext::new(send_far.clone(), recv_far, links, control.clone());
+        let task = spawn(ctx, send_far);
 
         Self {
             send,
             recv,
+            reclaim,
+            pending: 0,
             task,
+            control,
+            failure: None,
+            name,
+            priority: default(),
         }
     }
 
     /// Loan the [`World`] object to this task for a moment.
     /// 
-    /// This is done automatically by 
+    /// This is done automatically by [`FlowTasksPlugin`](crate::plugin::FlowTasksPlugin)
+    /// 
+    /// If the flow has been cancelled, its cancel hooks are run instead, and 
+    /// any request for the `World` is refused.
+    /// 
+    /// Flows started with [`FlowTaskManager::start_local`](crate::plugin::FlowTaskManager::start_local)
+    /// can only be lent the `World` by the plugin.
     pub fn loan_world(&mut self, world: &mut World) -> bool {
-        if self.recv.is_empty() { return false }
+        self.lend(world, None)
+    }
+
+    /// Same as [`loan_world`](Self::loan_world), but local flows are polled
+    /// through `local` while they have the `World`
+    pub(crate) fn lend(&mut self, world: &mut World, local: Option<&mut LocalFlow>) -> bool {
+        self.run_cancel_hooks(world);
+
+        if self.recv.is_empty() && self.pending == 0 { return false }
+
+        if !self.is_local() {
+            return block_on( self.load_world_call(world) )
+        }
+        // nothing else polls a local flow, so it would never return the `World`
+        let Some(flow) = local else { return false };
+        let poll_flow = poll_fn(|cx| -> Poll<bool> {
+            flow.poll(cx);
+            Poll::Pending
+        });
+        block_on( self.load_world_call(world).or(poll_flow) )
+    }
+
+    /// Cancel the flow if it has left its scope, running its cancel hooks 
+    /// straight away
+    pub(crate) fn check_scope(&self, world: &mut World) {
+        if self.control.is_cancelled() || self.control.in_scope(world) { return }
+        debug!("Cancelling flow {}, as it has left its scope", self.name);
+        self.control.cancel();
+        self.control.run_cancel_hooks(world, &self.name);
+    }
+
+    /// Run the flows cancel hooks, if it has been cancelled and they haven't run yet
+    pub(crate) fn run_cancel_hooks(&self, world: &mut World) {
+        if self.control.is_cancelled() {
+            self.control.run_cancel_hooks(world, &self.name);
+        }
+    }
+
+    /// Returns `true` if the flow has something for [`loan_world`](Self::loan_world)
+    /// to do, like asking for the [`World`], or having been cancelled
+    pub fn wants_world(&self) -> bool {
+        self.pending > 0 || !self.recv.is_empty() || self.control.is_cancelled()
+    }
+
+    /// Wait up to `linger` for the flow to ask for the [`World`] again. This 
+    /// spins, so `linger` should be kept short.
+    pub(crate) fn asks_again_within(&self, linger: Duration) -> bool {
+        // local flows are only polled by the thread that would be waiting
+        if self.is_local() { return self.wants_world() }
+
+        let deadline = Instant::now() + linger;
+        loop {
+            if self.control.is_cancelled() || self.is_finished() { return false }
+            if self.wants_world() { return true }
+            if Instant::now() >= deadline { return false }
+            yield_now();
+        }
+    }
 
-        block_on( self.load_world_call(world) )
+    /// Ask the flow to stop. See [`FlowTaskManager::stop`](crate::plugin::FlowTaskManager::stop)
+    pub fn cancel(&self) {
+        self.control.cancel();
+    }
+
+    /// Returns `true` if the flow has been asked to stop
+    pub fn is_cancelled(&self) -> bool {
+        self.control.is_cancelled()
+    }
+
+    /// The schedule this flow is pinned to. Flows that aren't pinned are lent 
+    /// the [`World`] in the schedules [`FlowTasksPlugin`](crate::plugin::FlowTasksPlugin)
+    /// was set up with.
+    /// 
+    /// See [`FlowTaskManager::start_in`](crate::plugin::FlowTaskManager::start_in)
+    pub fn schedule(&self) -> Option<InternedScheduleLabel> {
+        self.control.schedule()
+    }
+
+    /// How urgently this flow is lent the [`World`]. See [`FlowPriority`]
+    pub fn priority(&self) -> FlowPriority {
+        self.priority
+    }
+
+    /// Change how urgently this flow is lent the [`World`]. See [`FlowPriority`]
+    pub fn set_priority(&mut self, priority: FlowPriority) {
+        self.priority = priority;
+    }
+
+    /// The name of the flow. See [`Flow::name`]
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub(crate) fn set_name(&mut self, name: Cow<'static, str>) {
+        self.name = name;
+    }
+
+    /// Mark the flow as finished, as it's removed from the task list, and 
+    /// cancel the flows it started with [`FlowContext::spawn_child`]
+    pub(crate) fn wrap_up(&self) {
+        self.control.finished.store(true, Ordering::Release);
+        self.control.cancel_children();
     }
 
     /// Returns `true` if the task has completed.
     /// 
-    /// [`FlowTasksPlugin`]
+    /// Finished tasks are cleaned up by [`FlowTasksPlugin`](crate::plugin::FlowTasksPlugin)
     pub fn is_finished(&self) -> bool {
-        self.task.is_finished()
+        match &self.task {
+            RunnerTask::Thread(thread) => thread.is_finished(),
+            RunnerTask::Pool(task) => task.is_finished(),
+            RunnerTask::Local { finished, .. } => finished.load(Ordering::Acquire),
+        }
     }
 
-    async fn load_world_call(&self, world: &mut World) -> bool {
-        match self.recv.recv().await {
-            Ok(LTResult::RequestingWorld) => {
-                let msg = LTMsg::World(world as *mut _);
+    /// Returns `true` if the flow runs on the main thread. See 
+    /// [`FlowTaskManager::start_local`](crate::plugin::FlowTaskManager::start_local)
+    pub fn is_local(&self) -> bool {
+        matches!(self.task, RunnerTask::Local { .. })
+    }
 
-                if let Err(err) = self.send.send(msg).await {
-                    panic!("Load World Send: {err:?}");
-                }
-    
-                match self.recv.recv().await {
-                    Ok(LTResult::DoneWithWorld) => { 
-                        return false;
-                    },
-                    Ok(_) => println!("Load World Recv Bad"),
-                    Err(err) => panic!("Load World Recv: {err:?}"),
-                }
-    
-                false
-            },
-            Ok(LTResult::Finished) => true,
-            Ok(_) => todo!(),
-            Err(err) => todo!("Err: {err:?}"),
+    fn take_local_start(&self) -> Option<LocalStart> {
+        match &self.task {
+            RunnerTask::Local { start, .. } => start.lock().unwrap_or_else(|e| e.into_inner()).take(),
+            _ => None,
         }
     }
+
+    /// The error a finished flow failed with, if it failed or panicked.
+    pub(crate) fn take_failure(&mut self) -> Option<FlowError> {
+        while let Ok(msg) = self.recv.try_recv() {
+            if let LTResult::Finished(failure) = msg {
+                self.failure = failure;
+            }
+        }
+        self.failure.take()
+    }
+
+    /// Returns `true` if the flow has finished
+    async fn load_world_call(&mut self, world: &mut World) -> bool {
+        // catch up on everything the flow has sent. Requests are always sent
+        // before they're withdrawn, so taking the withdrawals first means
+        // every one of them has its request in what's read after
+        let withdrawn = self.control.take_withdrawn();
+        loop {
+            match self.recv.try_recv() {
+                Ok(LTResult::RequestingWorld) => self.pending += 1,
+                Ok(LTResult::Withdrawn) => self.pending = self.pending.saturating_sub(1),
+                Ok(LTResult::Nudge) => (),
+                Ok(LTResult::Finished(failure)) => {
+                    self.failure = failure;
+                    return true
+                },
+                Ok(LTResult::DoneWithWorld) => warn!("Flow returned the World without borrowing it"),
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Closed) => return true,
+            }
+        }
+        self.pending = self.pending.saturating_sub(withdrawn);
+
+        if self.pending == 0 { return false }
+        self.pending -= 1;
+
+        if self.control.is_cancelled() {
+            let _ = self.send.send(LTMsg::Cancelled).await;
+            return false
+        }
+
+        let msg = LTMsg::World(world as *mut _);
+        if self.send.send(msg).await.is_err() {
+            return true
+        }
+
+        loop {
+            match self.recv.recv().await {
+                Ok(LTResult::DoneWithWorld) => return false,
+                Ok(LTResult::RequestingWorld) => self.pending += 1,
+                Ok(LTResult::Withdrawn) => if self.withdrawn() { return false },
+                Ok(LTResult::Nudge) => (),
+                Ok(LTResult::Finished(failure)) => {
+                    self.failure = failure;
+                    return true
+                },
+                // the flow is gone, so it can't be holding on to the `World` anymore
+                Err(_) => return true,
+            }
+            // withdrawals that didn't fit in the channel are only counted, but 
+            // the channel was full then, so there's always a message after them
+            for _ in 0..self.control.take_withdrawn() {
+                if self.withdrawn() { return false }
+            }
+        }
+    }
+
+    /// Whoever asked for the `World` gave up waiting. If nobody else picked 
+    /// it up in their place, take it back, and return `true` to end the loan
+    fn withdrawn(&mut self) -> bool {
+        let reclaimed = matches!(self.reclaim.try_recv(), Ok(LTMsg::World(_)));
+        if reclaimed {
+            self.pending += 1;
+        }
+        self.pending = self.pending.saturating_sub(1);
+        reclaimed
+    }
 }
 
 
@@ -97,8 +674,39 @@ impl FlowTaskRunner {
 
 
 
+/// Creates a flow with `start`, runs it to the end, and reports how it went,
+/// to whoever is waiting on its result, and to its runner through `done`
+async fn report<Fut: Future>(
+    start: impl FnOnce() -> Fut,
+    result: Sender<Result<Fut::Output, FlowError>>,
+    check: fn(&Fut::Output) -> Option<FlowError>,
+    done: Sender<LTResult>,
+) {
+    // panicking and cancelled flows unwind out of whatever they were 
+    // doing, and are caught here so they can't take down the app. Any
+    // `WorldRef` they held is dropped on the way, returning the `World`.
+    // The flow is only created here too, as closures can panic before 
+    // they return their future
+    let out = match catch_unwind(AssertUnwindSafe(start)) {
+        Ok(flow) => AssertUnwindSafe(flow).catch_unwind().await.map_err(FlowError::from_panic),
+        Err(payload) => Err(FlowError::from_panic(payload)),
+    };
+
+    let failure = match &out {
+        Ok(out) => check(out),
+        Err(FlowError::Cancelled) => None,
+        Err(err) => Some(err.clone()),
+    };
+    // nobody may be listening, which is fine
+    let _ = result.try_send(out);
+
+    let _ = done.send(LTResult::Finished(failure)).await;
+}
+
+
 pub(crate) enum LTMsg {
     World(*mut World),
+    Cancelled,
 }
 
 unsafe impl Send for LTMsg { }
@@ -107,5 +715,11 @@ unsafe impl Sync for LTMsg { }
 pub(crate) enum LTResult {
     DoneWithWorld,
     RequestingWorld,
-    Finished,
+    /// A request for the `World` was dropped before it was answered
+    Withdrawn,
+    /// A request was withdrawn with [`FlowControl::withdraw`], which the 
+    /// runner should look at, as the channel was full
+    Nudge,
+    /// Carries the error the flow failed with, if any
+    Finished(Option<FlowError>),
 }
\ No newline at end of file
@@ -0,0 +1,242 @@
+//! Decides which flows are lent the [`World`](bevy::prelude::World), and in what order
+
+use std::{cmp::Reverse, collections::VecDeque, time::Duration};
+
+use bevy::utils::{hashbrown::HashMap, HashSet};
+
+use crate::{plugin::FlowTasksPlugin, runner::{FlowTaskId, FlowTaskRunner}};
+
+
+/// How urgently a flow should be lent the [`World`](bevy::prelude::World). Flows with a higher
+/// priority are lent it first. 
+/// 
+/// Set when starting a flow with [`FlowTaskManager::start_with_priority`](crate::plugin::FlowTaskManager::start_with_priority),
+/// or later with [`FlowTaskManager::set_priority`](crate::plugin::FlowTaskManager::set_priority)
+#[derive(Clone, Copy, Debug, Default, Hash, PartialEq, Eq, PartialOrd, Ord)]
+pub enum FlowPriority {
+    /// Work that can take as long as it needs, like reprocessing assets. 
+    /// Each background flow is lent the `World` at most once every few frames,
+    /// and only if there is loan budget left over after everything else.
+    /// See [`FlowTasksPlugin::background_interval`](crate::plugin::FlowTasksPlugin::background_interval)
+    Background,
+    /// Most flows
+    #[default]
+    Normal,
+    /// Flows that should be served before the rest
+    High,
+    /// Gameplay critical flows. These are lent the `World` even when the 
+    /// [loan budget](crate::plugin::FlowTasksPlugin::loan_budget) is spent
+    Critical,
+}
+
+/// Lends the [`World`](bevy::prelude::World) to flows by priority, then round-robin, within a 
+/// per-frame time budget.
+/// 
+/// Flows that are lent the `World` go to the back of the line, so flows that
+/// asked for it, but missed out because the budget was spent, are first in 
+/// line next frame.
+#[derive(Default)]
+pub(crate) struct LoanQueue {
+    line: VecDeque<FlowTaskId>,
+    budget: Option<Duration>,
+    spent: Duration,
+    frame: u32,
+    background_interval: u32,
+    /// How many loans a flow can get in a row. See [`FlowTasksPlugin::loans_per_run`]
+    loans_per_run: u32,
+    /// How long to wait for a flow to ask again, before moving on to the next one
+    linger: Duration,
+    /// The frame each background flow was last lent the `World`
+    background_loans: HashMap<FlowTaskId, u32>,
+}
+
+impl LoanQueue {
+    pub(crate) fn new(plugin: &FlowTasksPlugin) -> Self {
+        Self { 
+            budget: plugin.loan_budget, 
+            background_interval: plugin.background_interval, 
+            loans_per_run: plugin.loans_per_run.max(1),
+            linger: plugin.eager_linger,
+            ..Self::default()
+        }
+    }
+
+    /// Start a new frame, with the whole budget left
+    pub(crate) fn start_frame(&mut self) {
+        self.spent = Duration::ZERO;
+        self.frame = self.frame.wrapping_add(1);
+    }
+
+    /// Returns `true` if the flow `id` may be lent the `World` right now
+    pub(crate) fn can_lend(&self, id: FlowTaskId, priority: FlowPriority) -> bool {
+        match priority {
+            FlowPriority::Critical => true,
+            FlowPriority::Background => !self.is_spent() && self.background_loans.get(&id)
+                .is_none_or(|last| self.frame.wrapping_sub(*last) >= self.background_interval),
+            _ => !self.is_spent(),
+        }
+    }
+
+    /// Returns how long to linger for the flow `id` to ask again, having just 
+    /// been lent the `World` `loans` times in a row, or `None` if it shouldn't 
+    /// be lent the `World` again
+    pub(crate) fn can_lend_again(&self, id: FlowTaskId, priority: FlowPriority, loans: u32) -> Option<Duration> {
+        let again = loans < self.loans_per_run && self.can_lend(id, priority);
+        again.then_some(self.linger)
+    }
+
+    /// Returns `true` once no more loans should be given this frame
+    fn is_spent(&self) -> bool {
+        self.budget.is_some_and(|budget| self.spent >= budget)
+    }
+
+    /// The order flows should be lent the `World` in. Brings the line up to
+    /// date with `tasks` first: new flows join the back, and finished ones leave.
+    pub(crate) fn order(&mut self, tasks: &HashMap<FlowTaskId, FlowTaskRunner>) -> Vec<FlowTaskId> {
+        self.line.retain(|id| tasks.contains_key(id));
+        self.background_loans.retain(|id, _| tasks.contains_key(id));
+        let queued = self.line.iter().copied().collect::<HashSet<_>>();
+        self.line.extend(tasks.keys().filter(|id| !queued.contains(*id)));
+
+        let mut order = self.line.iter().copied().collect::<Vec<_>>();
+        // sorting is stable, so flows with the same priority keep their place in line
+        order.sort_by_key(|id| Reverse(tasks[id].priority()));
+        order
+    }
+
+    /// Take `took` out of this frames budget
+    pub(crate) fn spend(&mut self, took: Duration) {
+        self.spent += took;
+    }
+
+    /// Record that `id` was lent the `World` for `took`, sending it to the back of the line
+    pub(crate) fn lent(&mut self, id: FlowTaskId, priority: FlowPriority, took: Duration) {
+        self.spend(took);
+        if priority == FlowPriority::Background {
+            self.background_loans.insert(id, self.frame);
+        }
+        if let Some(index) = self.line.iter().position(|queued| *queued == id) {
+            self.line.remove(index);
+        }
+        self.line.push_back(id);
+    }
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use async_channel::{bounded, unbounded};
+    use bevy::{prelude::*, utils::hashbrown::HashMap};
+
+    use crate::{plugin::FlowTasksPlugin, runner::{FlowLinks, FlowTaskId, FlowTaskRunner}};
+
+    use super::{FlowPriority, LoanQueue};
+
+    /// A flow that's never started, so it only has a priority
+    fn runner(priority: FlowPriority) -> FlowTaskRunner {
+        let (result, _) = bounded(1);
+        let (waiters, _) = unbounded();
+        let (commands, _) = unbounded();
+        let links = FlowLinks { assets: None, waiters, commands, now: default() };
+        let mut runner = FlowTaskRunner::new_local(|_| async {}, links, &default(), result, default(), |_| None);
+        runner.set_priority(priority);
+        runner
+    }
+
+    fn tasks(priorities: &[(u64, FlowPriority)]) -> HashMap<FlowTaskId, FlowTaskRunner> {
+        priorities.iter().map(|(id, priority)| (FlowTaskId(*id), runner(*priority))).collect()
+    }
+
+    #[test]
+    fn order_goes_by_priority() {
+        let mut queue = LoanQueue::new(&FlowTasksPlugin::default());
+        let tasks = tasks(&[
+            (1, FlowPriority::Normal), 
+            (2, FlowPriority::High), 
+            (3, FlowPriority::Background), 
+            (4, FlowPriority::Critical),
+        ]);
+
+        assert_eq!(queue.order(&tasks), [4, 2, 1, 3].map(FlowTaskId));
+    }
+
+    #[test]
+    fn order_is_round_robin_within_a_priority() {
+        let mut queue = LoanQueue::new(&FlowTasksPlugin::default());
+        let tasks = tasks(&[(1, FlowPriority::Normal), (2, FlowPriority::Normal), (3, FlowPriority::High)]);
+
+        let order = queue.order(&tasks);
+        let (first, second) = (order[1], order[2]);
+        queue.lent(first, FlowPriority::Normal, Duration::ZERO);
+
+        assert_eq!(queue.order(&tasks), [FlowTaskId(3), second, first]);
+    }
+
+    #[test]
+    fn order_drops_finished_flows() {
+        let mut queue = LoanQueue::new(&FlowTasksPlugin::default());
+        let mut tasks = tasks(&[(1, FlowPriority::Normal), (2, FlowPriority::Background)]);
+        queue.order(&tasks);
+        queue.lent(FlowTaskId(2), FlowPriority::Background, Duration::ZERO);
+
+        tasks.remove(&FlowTaskId(2));
+        assert_eq!(queue.order(&tasks), [FlowTaskId(1)]);
+        assert!(queue.background_loans.is_empty());
+    }
+
+    #[test]
+    fn spent_budget_only_lends_to_critical_flows() {
+        let plugin = FlowTasksPlugin::default().with_loan_budget(Duration::from_millis(2));
+        let mut queue = LoanQueue::new(&plugin);
+        let id = FlowTaskId(1);
+
+        queue.spend(Duration::from_millis(1));
+        assert!(queue.can_lend(id, FlowPriority::Normal));
+        queue.spend(Duration::from_millis(1));
+        assert!(!queue.can_lend(id, FlowPriority::Background));
+        assert!(!queue.can_lend(id, FlowPriority::Normal));
+        assert!(!queue.can_lend(id, FlowPriority::High));
+        assert!(queue.can_lend(id, FlowPriority::Critical));
+
+        queue.start_frame();
+        assert!(queue.can_lend(id, FlowPriority::Normal));
+    }
+
+    #[test]
+    fn background_flows_wait_out_their_interval() {
+        let plugin = FlowTasksPlugin::default().with_background_interval(3);
+        let mut queue = LoanQueue::new(&plugin);
+        let (id, other) = (FlowTaskId(1), FlowTaskId(2));
+
+        assert!(queue.can_lend(id, FlowPriority::Background));
+        queue.lent(id, FlowPriority::Background, Duration::ZERO);
+        for _ in 0..3 {
+            assert!(!queue.can_lend(id, FlowPriority::Background));
+            // the interval is per flow
+            assert!(queue.can_lend(other, FlowPriority::Background));
+            queue.start_frame();
+        }
+        assert!(queue.can_lend(id, FlowPriority::Background));
+    }
+
+    #[test]
+    fn can_lend_again_up_to_loans_per_run() {
+        let linger = Duration::from_micros(50);
+        let plugin = FlowTasksPlugin::default()
+            .with_eager_loans(2, linger)
+            .with_loan_budget(Duration::from_millis(1));
+        let mut queue = LoanQueue::new(&plugin);
+        let id = FlowTaskId(1);
+
+        assert_eq!(queue.can_lend_again(id, FlowPriority::Normal, 1), Some(linger));
+        assert_eq!(queue.can_lend_again(id, FlowPriority::Normal, 2), None);
+
+        // lending again still has to fit in the budget
+        queue.spend(Duration::from_millis(1));
+        assert_eq!(queue.can_lend_again(id, FlowPriority::Normal, 1), None);
+        assert_eq!(queue.can_lend_again(id, FlowPriority::Critical, 1), Some(linger));
+    }
+}
@@ -0,0 +1,121 @@
+//! Waiting on several things at once, and giving up on waiting
+
+use std::{future::{Future, IntoFuture}, time::Duration};
+
+use bevy::tasks::futures_lite::FutureExt;
+
+use crate::{context::FlowContext, error::FlowError, waiter::FlowClock};
+
+
+/// Waits on several futures at once, and finishes with whichever is done first. 
+/// Created by [`FlowContext::select`].
+/// 
+/// Each future added with [`or`](Self::or) comes with a function that turns its
+/// output into the output of the whole select, which is how you tell which one 
+/// finished. When several finish at the same time, the one added first wins.
+/// The futures that didn't finish are dropped.
+/// 
+/// ```rust,no_run
+/// # use std::time::Duration;
+/// # use bevy::prelude::*;
+/// # use bevy_flow::prelude::*;
+/// # #[derive(Event)] struct PeerAnswered;
+/// # #[derive(Clone, Debug, Default, Hash, PartialEq, Eq, States)] enum Net { #[default] Online, Offline }
+/// enum Woke {
+///     Answered,
+///     WentOffline,
+/// }
+/// 
+/// async fn wait_for_peer(ctx: FlowContext) -> Result<(), FlowError> {
+///     let woke = ctx.select()
+///         .or(ctx.await_event(|_: &PeerAnswered| true), |_| Woke::Answered)
+///         .or(ctx.await_state(Net::Offline), |_| Woke::WentOffline)
+///         .timeout(Duration::from_secs(10))
+///         .await?;
+/// 
+///     match woke {
+///         Woke::Answered => info!("peer answered"),
+///         Woke::WentOffline => warn!("went offline"),
+///     }
+///     Ok(())
+/// }
+/// ```
+pub struct FlowSelect<'a, F> {
+    ctx: &'a FlowContext,
+    select: F,
+}
+
+impl<'a, T, F> FlowSelect<'a, F> 
+where
+    F: Future<Output = Result<T, FlowError>> + 'a,
+    T: 'a,
+{
+    pub(crate) fn new(ctx: &'a FlowContext, select: F) -> Self {
+        Self { ctx, select }
+    }
+
+    /// Also wait on `future`. If it finishes first, its output is passed 
+    /// through `map` to become the output of the select.
+    pub fn or<G, M>(self, future: G, map: M) -> FlowSelect<'a, impl Future<Output = Result<T, FlowError>> + 'a>
+    where
+        G: Future + 'a,
+        M: FnOnce(G::Output) -> T + 'a,
+    {
+        let branch = async move { Ok(map(future.await)) };
+        FlowSelect::new(self.ctx, self.select.or(branch))
+    }
+
+    /// Give up waiting after `timeout` has passed on [`Time<Real>`](bevy::time::Real),
+    /// in which case the select finishes with [`FlowError::Timeout`].
+    pub fn timeout(self, timeout: Duration) -> FlowSelect<'a, impl Future<Output = Result<T, FlowError>> + 'a> {
+        let ctx = self.ctx;
+        let branch = async move {
+            ctx.try_sleep_with(timeout, FlowClock::Real).await?;
+            Err(FlowError::Timeout)
+        };
+        FlowSelect::new(self.ctx, self.select.or(branch))
+    }
+}
+
+impl<'a, T, F> IntoFuture for FlowSelect<'a, F> 
+where
+    F: Future<Output = Result<T, FlowError>>,
+{
+    type Output = Result<T, FlowError>;
+    type IntoFuture = F;
+
+    fn into_future(self) -> Self::IntoFuture {
+        self.select
+    }
+}
+
+
+/// Adds flow specific combinators to every [`Future`]
+pub trait FlowFutureExt: Future + Sized {
+    /// Wait for this future, but give up after `timeout` has passed on 
+    /// [`Time<Real>`](bevy::time::Real). Timing out drops the future, 
+    /// and returns [`FlowError::Timeout`].
+    /// 
+    /// ```rust,no_run
+    /// # use std::time::Duration;
+    /// # use bevy::prelude::*;
+    /// # use bevy_flow::prelude::*;
+    /// # #[derive(Event)] struct PeerAnswered;
+    /// async fn wait_for_peer(ctx: FlowContext) -> Result<(), FlowError> {
+    ///     ctx.await_event(|_: &PeerAnswered| true)
+    ///         .with_timeout(&ctx, Duration::from_secs(10))
+    ///         .await
+    /// }
+    /// ```
+    fn with_timeout<'a>(self, ctx: &'a FlowContext, timeout: Duration) -> impl Future<Output = Result<Self::Output, FlowError>> + 'a
+    where
+        Self: 'a,
+    {
+        ctx.select()
+            .or(self, |out| out)
+            .timeout(timeout)
+            .into_future()
+    }
+}
+
+impl<F: Future> FlowFutureExt for F { }
@@ -0,0 +1,77 @@
+//! Starting flows from outside of systems, through the [`World`] or [`Commands`]
+
+use bevy::prelude::*;
+
+use crate::{error::FlowOutcome, flow::Flow, plugin::start_in_world, runner::FlowTaskId};
+
+
+/// Adds methods for starting flows to [`World`] and [`Commands`], for places
+/// [`FlowTaskManager`](crate::plugin::FlowTaskManager) can't be used, like
+/// observers and exclusive systems.
+///
+/// Flows started through [`Commands`] are started when the commands are
+/// applied, but their id is known straight away.
+///
+/// ```rust
+/// # use bevy::prelude::*;
+/// # use bevy_flow::prelude::*;
+/// #[derive(Event)]
+/// struct OpenDoor;
+///
+/// fn setup(mut commands: Commands) {
+///     commands.observe(|_trigger: Trigger<OpenDoor>, mut commands: Commands| {
+///         commands.start_flow(|ctx: FlowContext| async move {
+///             // ...
+///         });
+///     });
+/// }
+/// ```
+///
+/// # Panics
+///
+/// Starting a flow panics if the [`FlowTasksPlugin`](crate::plugin::FlowTasksPlugin)
+/// hasn't been added to the app. For [`Commands`], that's when they're applied.
+pub trait StartFlowExt {
+    /// Start a [`Flow`] that doesn't need any [`Input`](Flow::Input).
+    /// See [`FlowTaskManager::start_flow`](crate::plugin::FlowTaskManager::start_flow)
+    fn start_flow<F>(&mut self, flow: F) -> FlowTaskId
+    where
+        F: Flow<Input = ()>,
+        F::Output: FlowOutcome,
+    {
+        self.start_flow_with(flow, ())
+    }
+
+    /// Start a [`Flow`] with its [`Input`](Flow::Input).
+    /// See [`FlowTaskManager::start_flow_with`](crate::plugin::FlowTaskManager::start_flow_with)
+    fn start_flow_with<F>(&mut self, flow: F, input: F::Input) -> FlowTaskId
+    where
+        F: Flow,
+        F::Output: FlowOutcome;
+}
+
+impl StartFlowExt for World {
+    fn start_flow_with<F>(&mut self, flow: F, input: F::Input) -> FlowTaskId
+    where
+        F: Flow,
+        F::Output: FlowOutcome,
+    {
+        let id = FlowTaskId::next();
+        start_in_world(self, id, flow, input, default());
+        id
+    }
+}
+
+impl StartFlowExt for Commands<'_, '_> {
+    fn start_flow_with<F>(&mut self, flow: F, input: F::Input) -> FlowTaskId
+    where
+        F: Flow,
+        F::Output: FlowOutcome,
+    {
+        let id = FlowTaskId::next();
+        self.add(move |world: &mut World| {
+            start_in_world(world, id, flow, input, default());
+        });
+        id
+    }
+}
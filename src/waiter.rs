@@ -0,0 +1,136 @@
+//! Conditions flows are waiting on. These are checked on the bevy side by a 
+//! regular system, so a waiting flow doesn't need to borrow the [`World`]
+//! until there is actually something for it to do.
+
+use std::{sync::{Arc, Mutex}, time::Duration};
+
+use async_channel::{unbounded, Receiver, Sender};
+use bevy::{prelude::*, time::{Fixed, Real, Virtual}};
+
+use crate::cursor::EventCounts;
+
+
+/// Checks if a wait is over, returning `true` once it is. 
+/// 
+/// Checks are responsible for telling their flow the wait is over.
+pub(crate) type WaitCheck = Box<dyn FnMut(&World) -> bool + Send>;
+
+/// The end of the channel flows register their [`WaitCheck`]s through, 
+/// and the checks that are still waiting. 
+/// 
+/// The checks are kept here rather than in the system, so every schedule
+/// flows run in checks on all of them.
+#[derive(Resource)]
+pub(crate) struct FlowWaiters {
+    recv: Receiver<WaitCheck>,
+    waiting: Mutex<Vec<WaitCheck>>,
+    now: Arc<FlowNow>,
+}
+
+impl FlowWaiters {
+    /// Creates the resource, and the [`Sender`] flows should be given
+    pub(crate) fn new() -> (Self, Sender<WaitCheck>) {
+        let (send, recv) = unbounded();
+        (Self { recv, waiting: default(), now: default() }, send)
+    }
+
+    /// What flows should start their waits from
+    pub(crate) fn now(&self) -> Arc<FlowNow> {
+        self.now.clone()
+    }
+}
+
+/// The frame, the time on each [`FlowClock`], and the [`EventCounts`], as of 
+/// the last time the waiters were checked, or a flow was done with the [`World`].
+/// 
+/// Flows can't look at the [`World`] without borrowing it, so they start 
+/// their waits from this. Otherwise a wait would only start once it's first
+/// checked, which can be a frame after it was made.
+#[derive(Default)]
+pub(crate) struct FlowNow {
+    frame: Mutex<u32>,
+    clocks: Mutex<[Option<Duration>; 3]>,
+    events: EventCounts,
+}
+
+impl FlowNow {
+    /// Frames counted by the plugin, which moves on at the start of every frame
+    pub(crate) fn frame(&self) -> u32 {
+        *self.frame.lock().unwrap_or_else(|poison| poison.into_inner())
+    }
+
+    /// How much time had passed on `clock`, or [`None`] if it wasn't present
+    pub(crate) fn elapsed(&self, clock: FlowClock) -> Option<Duration> {
+        self.clocks.lock().unwrap_or_else(|poison| poison.into_inner())[clock as usize]
+    }
+
+    /// How many events of each type flows read had been sent
+    pub(crate) fn events(&self) -> &EventCounts {
+        &self.events
+    }
+
+    pub(crate) fn next_frame(&self) {
+        let mut frame = self.frame.lock().unwrap_or_else(|poison| poison.into_inner());
+        *frame = frame.wrapping_add(1);
+    }
+
+    pub(crate) fn update(&self, world: &World) {
+        let clocks = [FlowClock::Virtual, FlowClock::Real, FlowClock::Fixed]
+            .map(|clock| clock.elapsed(world));
+        *self.clocks.lock().unwrap_or_else(|poison| poison.into_inner()) = clocks;
+        self.events.update(world);
+    }
+}
+
+/// Checks on every registered [`WaitCheck`], dropping the ones that are done.
+/// 
+/// This only needs read access to the [`World`], so it doesn't halt the app
+/// like lending the `World` to flows does.
+pub(crate) fn check_waiters(world: &World) {
+    let Some(waiters) = world.get_resource::<FlowWaiters>() else { return };
+    waiters.now.update(world);
+    // checks catch their own panics, so poisoning is fine to ignore
+    let mut waiting = waiters.waiting.lock().unwrap_or_else(|poison| poison.into_inner());
+    while let Ok(check) = waiters.recv.try_recv() {
+        waiting.push(check);
+    }
+
+    waiting.retain_mut(|check| !check(world));
+}
+
+
+/// Which of bevy's [`Time`]s a flow sleeps by. 
+/// 
+/// See [`FlowContext::sleep_with`](crate::context::FlowContext::sleep_with)
+#[derive(Clone, Copy, Debug, Default, Hash, PartialEq, Eq)]
+pub enum FlowClock {
+    /// [`Time<Virtual>`]. This is the clock gameplay runs on, so pausing it,
+    /// or slowing it down, also pauses or slows sleeping flows.
+    #[default]
+    Virtual,
+    /// [`Time<Real>`]. Keeps going while the game is paused.
+    Real,
+    /// [`Time<Fixed>`]. Advances along with the [`FixedUpdate`] schedule.
+    Fixed,
+}
+
+impl FlowClock {
+    /// How much time has passed on this clock, or [`None`] if the clock 
+    /// isn't present
+    pub(crate) fn elapsed(&self, world: &World) -> Option<Duration> {
+        match self {
+            Self::Virtual => world.get_resource::<Time<Virtual>>().map(Time::elapsed),
+            Self::Real => world.get_resource::<Time<Real>>().map(Time::elapsed),
+            Self::Fixed => world.get_resource::<Time<Fixed>>().map(Time::elapsed),
+        }
+    }
+
+    /// The type name of this clocks [`Time`], for error messages
+    pub(crate) fn type_name(&self) -> &'static str {
+        match self {
+            Self::Virtual => "Time<Virtual>",
+            Self::Real => "Time<Real>",
+            Self::Fixed => "Time<Fixed>",
+        }
+    }
+}